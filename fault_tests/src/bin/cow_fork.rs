@@ -0,0 +1,57 @@
+//! Fork, then write different values to the same page in the parent and the child, which
+//! share it copy-on-write until then. Each side must only see its own write.
+#![no_std]
+#![no_main]
+
+use fault_tests::*;
+
+/// a page of the program data, mapped in both tasks after the fork
+static mut PAGE: [u8; 4096] = [0; 4096];
+
+/// exit code of the child when it saw what it expected
+const CHILD_EXIT_CODE: i32 = 7;
+
+fn read_page() -> u8 {
+    unsafe { core::ptr::read_volatile(core::ptr::addr_of!(PAGE[0])) }
+}
+
+fn write_page(value: u8) {
+    unsafe { core::ptr::write_volatile(core::ptr::addr_of_mut!(PAGE[0]), value) }
+}
+
+#[no_mangle]
+fn main() -> i32 {
+    write_page(1);
+    let pid = fork();
+    if pid < 0 {
+        println!("cow_fork: fork failed");
+        return -1;
+    }
+    if pid == 0 {
+        if read_page() != 1 {
+            println!("cow_fork: the child does not see the value written before fork");
+            exit(-1);
+        }
+        write_page(2);
+        // let the parent write its own value meanwhile
+        for _ in 0..8 {
+            yield_();
+        }
+        if read_page() != 2 {
+            println!("cow_fork: the child sees {}, expected 2", read_page());
+            exit(-1);
+        }
+        exit(CHILD_EXIT_CODE);
+    }
+    write_page(3);
+    let exit_code = wait(pid as usize);
+    if read_page() != 3 {
+        println!("cow_fork: the parent sees {}, expected 3", read_page());
+        return -1;
+    }
+    if exit_code != CHILD_EXIT_CODE {
+        println!("cow_fork: the child exited with {}", exit_code);
+        return -1;
+    }
+    0
+}
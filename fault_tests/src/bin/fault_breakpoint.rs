@@ -0,0 +1,13 @@
+//! Hit a breakpoint without a debugger
+#![no_std]
+#![no_main]
+
+use core::arch::asm;
+use fault_tests::*;
+
+#[no_mangle]
+fn main() -> i32 {
+    unsafe { asm!("ebreak") };
+    println!("went past a breakpoint");
+    0
+}
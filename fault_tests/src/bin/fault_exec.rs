@@ -0,0 +1,16 @@
+//! Jump into data, which is not executable
+#![no_std]
+#![no_main]
+
+use fault_tests::*;
+
+/// `ret`, would run if data were executable
+static mut CODE: [u32; 1] = [0x0000_8067];
+
+#[no_mangle]
+fn main() -> i32 {
+    let code: fn() = unsafe { core::mem::transmute(core::ptr::addr_of!(CODE) as usize) };
+    code();
+    println!("ran code from data");
+    0
+}
@@ -0,0 +1,27 @@
+//! Fault with a handler for SIGSEGV, which exits with a code of its own
+#![no_std]
+#![no_main]
+
+use fault_tests::*;
+
+extern "C" fn on_segv(signum: usize) -> ! {
+    exit(if signum == SIGSEGV {
+        HANDLED_EXIT_CODE
+    } else {
+        -1
+    })
+}
+
+#[no_mangle]
+fn main() -> i32 {
+    let action = SignalAction {
+        handler: on_segv as usize,
+        restorer: 0,
+        mask: 0,
+    };
+    if sigaction(SIGSEGV, &action) != 0 {
+        return -1;
+    }
+    unsafe { core::ptr::write_volatile(UNMAPPED as *mut usize, 0) };
+    0
+}
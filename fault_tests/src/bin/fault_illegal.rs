@@ -0,0 +1,16 @@
+//! Run an illegal instruction, then a privileged one
+#![no_std]
+#![no_main]
+
+use core::arch::asm;
+use fault_tests::*;
+
+#[no_mangle]
+fn main() -> i32 {
+    unsafe {
+        asm!("unimp");
+        asm!("csrw sstatus, zero");
+    }
+    println!("ran illegal instructions");
+    0
+}
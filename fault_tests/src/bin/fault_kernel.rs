@@ -0,0 +1,12 @@
+//! Read the trap context page, which is mapped for the kernel only
+#![no_std]
+#![no_main]
+
+use fault_tests::*;
+
+#[no_mangle]
+fn main() -> i32 {
+    let value = unsafe { core::ptr::read_volatile(TRAP_CONTEXT_BASE as *const usize) };
+    println!("read {:#x} in the trap context", value);
+    0
+}
@@ -0,0 +1,12 @@
+//! Read from an address where nothing is mapped
+#![no_std]
+#![no_main]
+
+use fault_tests::*;
+
+#[no_mangle]
+fn main() -> i32 {
+    let value = unsafe { core::ptr::read_volatile(UNMAPPED as *const usize) };
+    println!("read {} at {:#x}", value, UNMAPPED);
+    0
+}
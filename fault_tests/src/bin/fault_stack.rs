@@ -0,0 +1,18 @@
+//! Recurse until the user stack overflows
+#![no_std]
+#![no_main]
+
+use fault_tests::*;
+
+#[inline(never)]
+fn recurse(depth: usize) -> usize {
+    let frame = [depth; 64];
+    core::hint::black_box(&frame);
+    recurse(depth + 1) + frame[depth % 64]
+}
+
+#[no_mangle]
+fn main() -> i32 {
+    println!("depth {}", recurse(0));
+    0
+}
@@ -0,0 +1,12 @@
+//! Write to the read-only code of the program
+#![no_std]
+#![no_main]
+
+use fault_tests::*;
+
+#[no_mangle]
+fn main() -> i32 {
+    unsafe { core::ptr::write_volatile(main as usize as *mut u32, 0) };
+    println!("wrote over main");
+    0
+}
@@ -0,0 +1,65 @@
+//! Run every test program and check how it ended
+#![no_std]
+#![no_main]
+
+use fault_tests::*;
+
+/// program, expected exit code
+const TESTS: &[(&str, i32)] = &[
+    ("fault_load\0", SIGNAL_EXIT_BASE + SIGSEGV as i32),
+    ("fault_store\0", SIGNAL_EXIT_BASE + SIGSEGV as i32),
+    ("fault_kernel\0", SIGNAL_EXIT_BASE + SIGSEGV as i32),
+    ("fault_exec\0", SIGNAL_EXIT_BASE + SIGSEGV as i32),
+    ("fault_stack\0", SIGNAL_EXIT_BASE + SIGSEGV as i32),
+    ("fault_illegal\0", SIGNAL_EXIT_BASE + SIGILL as i32),
+    ("fault_breakpoint\0", SIGNAL_EXIT_BASE + SIGTRAP as i32),
+    ("fault_handled\0", HANDLED_EXIT_CODE),
+    // not a fault, the copy-on-write pages of fork
+    ("cow_fork\0", 0),
+];
+
+/// address, length of buffers system calls must refuse without faulting
+const BAD_BUFFERS: &[(usize, usize)] = &[
+    (UNMAPPED, 8),
+    (TRAP_CONTEXT_BASE, 8),
+    // ends past the 39 bits of a virtual address
+    (0x7f_ffff_fff8, 16),
+    // a range wrapping around the address space
+    (usize::MAX - 3, 8),
+];
+
+#[no_mangle]
+fn main() -> i32 {
+    let mut failed = 0;
+    for &(addr, len) in BAD_BUFFERS {
+        let ret = write_from(1, addr, len);
+        if ret == EFAULT {
+            println!("[ ok ] write from {:#x} failed with EFAULT", addr);
+        } else {
+            println!("[fail] write from {:#x} returned {}", addr, ret);
+            failed += 1;
+        }
+    }
+    for &(program, expected) in TESTS {
+        let name = program.trim_end_matches('\0');
+        let pid = fork();
+        if pid == 0 {
+            exec(program);
+            println!("can not exec {}", name);
+            exit(-1);
+        }
+        let exit_code = wait(pid as usize);
+        if exit_code == expected {
+            println!("[ ok ] {} exited with {}", name, exit_code);
+        } else {
+            println!(
+                "[fail] {} exited with {}, expected {}",
+                name, exit_code, expected
+            );
+            failed += 1;
+        }
+    }
+    let total = BAD_BUFFERS.len() + TESTS.len();
+    println!("fault_tests: {} of {} passed", total - failed, total);
+    failed as i32
+}
@@ -0,0 +1,141 @@
+//! Programs that fault on purpose, and `fault_tests` running them
+//!
+//! Each program is expected to be terminated by the kernel with a given exit code instead
+//! of bringing the whole system down. They are put in the initramfs by `make initramfs`.
+//! They only need the few system calls below, so they do not depend on the user library.
+#![no_std]
+
+use core::arch::asm;
+use core::fmt::{self, Write};
+
+const SYSCALL_WRITE: usize = 64;
+const SYSCALL_EXIT: usize = 93;
+const SYSCALL_YIELD: usize = 124;
+const SYSCALL_SIGACTION: usize = 134;
+const SYSCALL_FORK: usize = 220;
+const SYSCALL_EXEC: usize = 221;
+const SYSCALL_WAITPID: usize = 260;
+
+/// exit code of a task terminated by the signal n is 128 + n
+pub const SIGNAL_EXIT_BASE: i32 = 128;
+pub const SIGILL: usize = 4;
+pub const SIGTRAP: usize = 5;
+pub const SIGSEGV: usize = 11;
+
+/// exit code of `fault_handled`, from its handler
+pub const HANDLED_EXIT_CODE: i32 = 42;
+
+/// below the program, nothing is ever mapped there
+pub const UNMAPPED: usize = 0x1000;
+/// the trap context, mapped for the kernel only
+pub const TRAP_CONTEXT_BASE: usize = usize::MAX - 2 * 4096 + 1;
+
+/// return value of a system call given a bad address
+pub const EFAULT: isize = -14;
+
+fn syscall(id: usize, args: [usize; 3]) -> isize {
+    let ret: isize;
+    unsafe {
+        asm!(
+            "ecall",
+            inlateout("a0") args[0] => ret,
+            in("a1") args[1],
+            in("a2") args[2],
+            in("a7") id,
+        );
+    }
+    ret
+}
+
+pub fn write(fd: usize, buf: &[u8]) -> isize {
+    write_from(fd, buf.as_ptr() as usize, buf.len())
+}
+
+/// write `len` bytes from any address, even a bad one
+pub fn write_from(fd: usize, addr: usize, len: usize) -> isize {
+    syscall(SYSCALL_WRITE, [fd, addr, len])
+}
+
+pub fn exit(exit_code: i32) -> ! {
+    syscall(SYSCALL_EXIT, [exit_code as usize, 0, 0]);
+    unreachable!()
+}
+
+pub fn yield_() -> isize {
+    syscall(SYSCALL_YIELD, [0; 3])
+}
+
+pub fn fork() -> isize {
+    syscall(SYSCALL_FORK, [0; 3])
+}
+
+/// `path` ends with a nul
+pub fn exec(path: &str) -> isize {
+    syscall(SYSCALL_EXEC, [path.as_ptr() as usize, 0, 0])
+}
+
+/// wait for the child `pid` to exit, return its exit code
+pub fn wait(pid: usize) -> i32 {
+    let mut exit_code = 0;
+    loop {
+        match syscall(
+            SYSCALL_WAITPID,
+            [pid, &mut exit_code as *mut i32 as usize, 0],
+        ) {
+            -2 => {
+                yield_();
+            }
+            _ => return exit_code,
+        }
+    }
+}
+
+/// the `SignalAction` of the kernel
+#[repr(C)]
+pub struct SignalAction {
+    pub handler: usize,
+    pub restorer: usize,
+    pub mask: u32,
+}
+
+pub fn sigaction(signum: usize, action: &SignalAction) -> isize {
+    syscall(
+        SYSCALL_SIGACTION,
+        [signum, action as *const SignalAction as usize, 0],
+    )
+}
+
+struct Stdout;
+
+impl Write for Stdout {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        write(1, s.as_bytes());
+        Ok(())
+    }
+}
+
+pub fn print(args: fmt::Arguments) {
+    Stdout.write_fmt(args).unwrap();
+}
+
+#[macro_export]
+macro_rules! println {
+    ($fmt: literal $(, $($arg: tt)+)?) => {
+        $crate::print(format_args!(concat!($fmt, "\n") $(, $($arg)+)?))
+    }
+}
+
+#[no_mangle]
+#[link_section = ".text.entry"]
+pub extern "C" fn _start() -> ! {
+    extern "Rust" {
+        fn main() -> i32;
+    }
+    exit(unsafe { main() })
+}
+
+#[panic_handler]
+fn panic(info: &core::panic::PanicInfo) -> ! {
+    println!("panic: {}", info);
+    exit(-1)
+}
@@ -0,0 +1,96 @@
+//! Build a simple_fs image from files on the host
+//!
+//! usage: sfs_pack -o <image> [-s <size in MiB>] <file>...
+//!
+//! Every file is put in the root directory under its file name.
+use simple_fs::{BlockDevice, DiskInodeType, SimpleFileSystem, BLOCK_SZ};
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::process::exit;
+use std::sync::{Arc, Mutex};
+
+const DEFAULT_SIZE_MIB: usize = 16;
+
+struct BlockFile(Mutex<File>);
+
+impl BlockDevice for BlockFile {
+    fn read_block(&self, block_id: usize, buf: &mut [u8]) {
+        let mut file = self.0.lock().unwrap();
+        file.seek(SeekFrom::Start((block_id * BLOCK_SZ) as u64))
+            .expect("Error when seeking!");
+        file.read_exact(buf).expect("Error when reading!");
+    }
+
+    fn write_block(&self, block_id: usize, buf: &[u8]) {
+        let mut file = self.0.lock().unwrap();
+        file.seek(SeekFrom::Start((block_id * BLOCK_SZ) as u64))
+            .expect("Error when seeking!");
+        file.write_all(buf).expect("Error when writing!");
+    }
+}
+
+fn usage() -> ! {
+    eprintln!("usage: sfs_pack -o <image> [-s <size in MiB>] <file>...");
+    exit(1);
+}
+
+fn main() {
+    let mut image = None;
+    let mut size_mib = DEFAULT_SIZE_MIB;
+    let mut files = Vec::new();
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-o" => image = Some(PathBuf::from(args.next().unwrap_or_else(|| usage()))),
+            "-s" => {
+                size_mib = args
+                    .next()
+                    .and_then(|size| size.parse().ok())
+                    .unwrap_or_else(|| usage())
+            }
+            _ => files.push(PathBuf::from(arg)),
+        }
+    }
+    let image = image.unwrap_or_else(|| usage());
+
+    let total_blocks = size_mib * 1024 * 1024 / BLOCK_SZ;
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(&image)
+        .expect("Cannot create the image!");
+    file.set_len((total_blocks * BLOCK_SZ) as u64).unwrap();
+    let block_file: Arc<dyn BlockDevice> = Arc::new(BlockFile(Mutex::new(file)));
+    let fs = SimpleFileSystem::create(block_file, total_blocks as u32, 1);
+    let root = SimpleFileSystem::root_inode(&fs);
+
+    for path in files.iter() {
+        let name = Path::new(path)
+            .file_name()
+            .and_then(|name| name.to_str())
+            .expect("Invalid file name!");
+        let data = std::fs::read(path).unwrap_or_else(|err| {
+            eprintln!("Cannot read {}: {}", path.display(), err);
+            exit(1);
+        });
+        let inode = root.create(name, DiskInodeType::File).unwrap_or_else(|| {
+            eprintln!("Cannot create {} in the image", name);
+            exit(1);
+        });
+        if inode.write_at(0, &data) != data.len() {
+            eprintln!("Image is full when writing {}", name);
+            exit(1);
+        }
+    }
+
+    // read everything back, the image is only as good as what the kernel will see
+    for name in root.ls() {
+        let inode = root.find(&name).unwrap();
+        let mut data = vec![0u8; inode.size()];
+        assert_eq!(inode.read_at(0, &mut data), data.len());
+        println!("{:>10} {}", data.len(), name);
+    }
+}
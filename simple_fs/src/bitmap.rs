@@ -0,0 +1,130 @@
+use crate::block_dev::{modify_block, BlockDevice};
+use crate::BLOCK_SZ;
+
+type BitmapBlock = [u64; BLOCK_SZ / 8];
+
+/// bits covered by one block of the bitmap
+pub(crate) const BLOCK_BITS: usize = BLOCK_SZ * 8;
+
+/// Allocation bitmap of `bits` bits, stored in the blocks following `start_block_id`
+pub(crate) struct Bitmap {
+    start_block_id: usize,
+    bits: usize,
+}
+
+impl Bitmap {
+    pub fn new(start_block_id: usize, bits: usize) -> Self {
+        Self {
+            start_block_id,
+            bits,
+        }
+    }
+
+    /// number of blocks the bitmap takes
+    pub fn blocks(&self) -> usize {
+        (self.bits + BLOCK_BITS - 1) / BLOCK_BITS
+    }
+
+    /// set the first clear bit and return its index
+    pub fn alloc(&self, device: &dyn BlockDevice) -> Option<usize> {
+        for block in 0..self.blocks() {
+            // bits past the end of the last block are never used
+            let limit = self.bits - block * BLOCK_BITS;
+            let bit = modify_block(
+                device,
+                self.start_block_id + block,
+                0,
+                |bitmap: &mut BitmapBlock| {
+                    let (index, bits) = bitmap
+                        .iter_mut()
+                        .enumerate()
+                        .find(|(_, bits)| **bits != u64::MAX)?;
+                    let inner = bits.trailing_ones() as usize;
+                    if index * 64 + inner >= limit {
+                        return None;
+                    }
+                    *bits |= 1 << inner;
+                    Some(index * 64 + inner)
+                },
+            );
+            if let Some(bit) = bit {
+                return Some(block * BLOCK_BITS + bit);
+            }
+        }
+        None
+    }
+
+    pub fn dealloc(&self, device: &dyn BlockDevice, bit: usize) {
+        let (block, bit) = (bit / BLOCK_BITS, bit % BLOCK_BITS);
+        modify_block(
+            device,
+            self.start_block_id + block,
+            0,
+            |bitmap: &mut BitmapBlock| {
+                assert!(bitmap[bit / 64] & (1 << (bit % 64)) != 0);
+                bitmap[bit / 64] &= !(1 << (bit % 64));
+            },
+        );
+    }
+
+    /// number of bits, free or not
+    pub fn maximum(&self) -> usize {
+        self.bits
+    }
+
+    #[cfg(test)]
+    pub fn start_block_id(&self) -> usize {
+        self.start_block_id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block_dev::MemDevice;
+
+    #[test]
+    fn alloc_takes_the_lowest_free_bit() {
+        let device = MemDevice::new(2);
+        let bitmap = Bitmap::new(1, 100);
+        assert_eq!(bitmap.alloc(device.as_ref()), Some(0));
+        assert_eq!(bitmap.alloc(device.as_ref()), Some(1));
+        assert_eq!(bitmap.alloc(device.as_ref()), Some(2));
+        bitmap.dealloc(device.as_ref(), 1);
+        assert_eq!(bitmap.alloc(device.as_ref()), Some(1));
+        assert_eq!(bitmap.alloc(device.as_ref()), Some(3));
+    }
+
+    #[test]
+    fn alloc_stops_at_the_last_bit() {
+        let device = MemDevice::new(2);
+        let bitmap = Bitmap::new(1, 70);
+        for bit in 0..70 {
+            assert_eq!(bitmap.alloc(device.as_ref()), Some(bit));
+        }
+        assert_eq!(bitmap.alloc(device.as_ref()), None);
+    }
+
+    #[test]
+    fn alloc_goes_on_in_the_next_block() {
+        let device = MemDevice::new(3);
+        let bitmap = Bitmap::new(1, BLOCK_BITS + 1);
+        assert_eq!(bitmap.blocks(), 2);
+        for bit in 0..=BLOCK_BITS {
+            assert_eq!(bitmap.alloc(device.as_ref()), Some(bit));
+        }
+        assert_eq!(bitmap.alloc(device.as_ref()), None);
+        // the block before the bitmap is left alone
+        let mut block = [0u8; BLOCK_SZ];
+        device.read_block(0, &mut block);
+        assert!(block.iter().all(|&byte| byte == 0));
+    }
+
+    #[test]
+    #[should_panic]
+    fn dealloc_of_a_free_bit_panics() {
+        let device = MemDevice::new(2);
+        let bitmap = Bitmap::new(1, 100);
+        bitmap.dealloc(device.as_ref(), 5);
+    }
+}
@@ -0,0 +1,63 @@
+use crate::BLOCK_SZ;
+use core::any::Any;
+
+/// Device the file system lives on, addressed in blocks of `BLOCK_SZ` bytes
+pub trait BlockDevice: Send + Sync + Any {
+    fn read_block(&self, block_id: usize, buf: &mut [u8]);
+    fn write_block(&self, block_id: usize, buf: &[u8]);
+}
+
+#[repr(C, align(8))]
+struct AlignedBlock([u8; BLOCK_SZ]);
+
+/// read block `block_id` and give `f` the value of type `T` at `offset` in it
+pub(crate) fn read_block<T, V>(
+    device: &dyn BlockDevice,
+    block_id: usize,
+    offset: usize,
+    f: impl FnOnce(&T) -> V,
+) -> V {
+    assert!(offset + core::mem::size_of::<T>() <= BLOCK_SZ);
+    let mut block = AlignedBlock([0; BLOCK_SZ]);
+    device.read_block(block_id, &mut block.0);
+    let value = unsafe { &*(block.0.as_ptr().add(offset) as *const T) };
+    f(value)
+}
+
+/// like `read_block`, and write the block back once `f` has modified the value
+pub(crate) fn modify_block<T, V>(
+    device: &dyn BlockDevice,
+    block_id: usize,
+    offset: usize,
+    f: impl FnOnce(&mut T) -> V,
+) -> V {
+    assert!(offset + core::mem::size_of::<T>() <= BLOCK_SZ);
+    let mut block = AlignedBlock([0; BLOCK_SZ]);
+    device.read_block(block_id, &mut block.0);
+    let value = unsafe { &mut *(block.0.as_mut_ptr().add(offset) as *mut T) };
+    let ret = f(value);
+    device.write_block(block_id, &block.0);
+    ret
+}
+
+/// A device held in memory, for the tests
+#[cfg(test)]
+pub(crate) struct MemDevice(spin::Mutex<alloc::vec::Vec<[u8; BLOCK_SZ]>>);
+
+#[cfg(test)]
+impl MemDevice {
+    pub fn new(blocks: usize) -> alloc::sync::Arc<Self> {
+        alloc::sync::Arc::new(Self(spin::Mutex::new(alloc::vec![[0; BLOCK_SZ]; blocks])))
+    }
+}
+
+#[cfg(test)]
+impl BlockDevice for MemDevice {
+    fn read_block(&self, block_id: usize, buf: &mut [u8]) {
+        buf.copy_from_slice(&self.0.lock()[block_id]);
+    }
+
+    fn write_block(&self, block_id: usize, buf: &[u8]) {
+        self.0.lock()[block_id].copy_from_slice(buf);
+    }
+}
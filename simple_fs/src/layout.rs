@@ -0,0 +1,443 @@
+use crate::block_dev::{modify_block, read_block, BlockDevice};
+use crate::BLOCK_SZ;
+use alloc::vec::Vec;
+
+/// "SFS1"
+const SFS_MAGIC: u32 = 0x3153_4653;
+/// longest file name, without the trailing nul
+pub const NAME_LENGTH_LIMIT: usize = 27;
+const INODE_DIRECT_COUNT: usize = 28;
+/// block ids held by an index block
+const INDIRECT_COUNT: usize = BLOCK_SZ / 4;
+const DIRECT_BOUND: usize = INODE_DIRECT_COUNT;
+const INDIRECT1_BOUND: usize = DIRECT_BOUND + INDIRECT_COUNT;
+const INDIRECT2_BOUND: usize = INDIRECT1_BOUND + INDIRECT_COUNT * INDIRECT_COUNT;
+
+type IndirectBlock = [u32; INDIRECT_COUNT];
+type DataBlock = [u8; BLOCK_SZ];
+
+#[repr(C)]
+pub struct SuperBlock {
+    magic: u32,
+    pub total_blocks: u32,
+    pub inode_bitmap_blocks: u32,
+    pub inode_area_blocks: u32,
+    pub data_bitmap_blocks: u32,
+    pub data_area_blocks: u32,
+}
+
+impl SuperBlock {
+    pub fn initialize(
+        &mut self,
+        total_blocks: u32,
+        inode_bitmap_blocks: u32,
+        inode_area_blocks: u32,
+        data_bitmap_blocks: u32,
+        data_area_blocks: u32,
+    ) {
+        *self = Self {
+            magic: SFS_MAGIC,
+            total_blocks,
+            inode_bitmap_blocks,
+            inode_area_blocks,
+            data_bitmap_blocks,
+            data_area_blocks,
+        }
+    }
+
+    pub fn is_valid(&self) -> bool {
+        self.magic == SFS_MAGIC
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum DiskInodeType {
+    File = 1,
+    Directory = 2,
+}
+
+/// Inode as stored in the inode area, four of them fit in a block
+#[repr(C)]
+pub struct DiskInode {
+    pub size: u32,
+    type_: u32,
+    direct: [u32; INODE_DIRECT_COUNT],
+    /// index block of the following `INDIRECT_COUNT` data blocks
+    indirect1: u32,
+    /// index block of index blocks for the rest
+    indirect2: u32,
+}
+
+impl DiskInode {
+    pub fn initialize(&mut self, type_: DiskInodeType) {
+        self.size = 0;
+        self.type_ = type_ as u32;
+        self.direct = [0; INODE_DIRECT_COUNT];
+        self.indirect1 = 0;
+        self.indirect2 = 0;
+    }
+
+    pub fn is_dir(&self) -> bool {
+        self.type_ == DiskInodeType::Directory as u32
+    }
+
+    fn data_blocks_of(size: u32) -> usize {
+        (size as usize + BLOCK_SZ - 1) / BLOCK_SZ
+    }
+
+    pub fn data_blocks(&self) -> usize {
+        Self::data_blocks_of(self.size)
+    }
+
+    /// data and index blocks of a file of `size` bytes
+    pub fn total_blocks(size: u32) -> usize {
+        let data_blocks = Self::data_blocks_of(size);
+        let mut total = data_blocks;
+        if data_blocks > DIRECT_BOUND {
+            total += 1;
+        }
+        if data_blocks > INDIRECT1_BOUND {
+            total += 1 + (data_blocks - INDIRECT1_BOUND + INDIRECT_COUNT - 1) / INDIRECT_COUNT;
+        }
+        total
+    }
+
+    /// blocks to allocate to grow the file to `new_size` bytes
+    pub fn blocks_num_needed(&self, new_size: u32) -> usize {
+        assert!(new_size >= self.size);
+        Self::total_blocks(new_size) - Self::total_blocks(self.size)
+    }
+
+    /// largest size a file can have
+    pub fn max_size() -> usize {
+        INDIRECT2_BOUND * BLOCK_SZ
+    }
+
+    /// block id of the `inner_id`th data block of the file
+    pub fn get_block_id(&self, inner_id: usize, device: &dyn BlockDevice) -> u32 {
+        if inner_id < DIRECT_BOUND {
+            self.direct[inner_id]
+        } else if inner_id < INDIRECT1_BOUND {
+            read_block(
+                device,
+                self.indirect1 as usize,
+                0,
+                |indirect: &IndirectBlock| indirect[inner_id - DIRECT_BOUND],
+            )
+        } else {
+            let index = inner_id - INDIRECT1_BOUND;
+            let indirect1 = read_block(
+                device,
+                self.indirect2 as usize,
+                0,
+                |indirect: &IndirectBlock| indirect[index / INDIRECT_COUNT],
+            );
+            read_block(device, indirect1 as usize, 0, |indirect: &IndirectBlock| {
+                indirect[index % INDIRECT_COUNT]
+            })
+        }
+    }
+
+    /// Grow the file to `new_size` bytes, `new_blocks` are the zeroed blocks
+    /// counted by `blocks_num_needed`, used for data and index blocks in order.
+    pub fn increase_size(&mut self, new_size: u32, new_blocks: Vec<u32>, device: &dyn BlockDevice) {
+        let mut current = self.data_blocks();
+        self.size = new_size;
+        let total = self.data_blocks();
+        let mut new_blocks = new_blocks.into_iter();
+        let mut next = || new_blocks.next().unwrap();
+        while current < total {
+            if current < DIRECT_BOUND {
+                self.direct[current] = next();
+            } else if current < INDIRECT1_BOUND {
+                if current == DIRECT_BOUND {
+                    self.indirect1 = next();
+                }
+                let block = next();
+                modify_block(
+                    device,
+                    self.indirect1 as usize,
+                    0,
+                    |indirect: &mut IndirectBlock| indirect[current - DIRECT_BOUND] = block,
+                );
+            } else {
+                if current == INDIRECT1_BOUND {
+                    self.indirect2 = next();
+                }
+                let index = current - INDIRECT1_BOUND;
+                if index % INDIRECT_COUNT == 0 {
+                    let indirect1 = next();
+                    modify_block(
+                        device,
+                        self.indirect2 as usize,
+                        0,
+                        |indirect: &mut IndirectBlock| indirect[index / INDIRECT_COUNT] = indirect1,
+                    );
+                }
+                let indirect1 = read_block(
+                    device,
+                    self.indirect2 as usize,
+                    0,
+                    |indirect: &IndirectBlock| indirect[index / INDIRECT_COUNT],
+                );
+                let block = next();
+                modify_block(
+                    device,
+                    indirect1 as usize,
+                    0,
+                    |indirect: &mut IndirectBlock| indirect[index % INDIRECT_COUNT] = block,
+                );
+            }
+            current += 1;
+        }
+    }
+
+    /// Shrink the file to nothing, return the data and index blocks it used.
+    pub fn clear_size(&mut self, device: &dyn BlockDevice) -> Vec<u32> {
+        let data_blocks = self.data_blocks();
+        let mut blocks = Vec::new();
+        for block in self.direct.iter_mut().take(data_blocks) {
+            blocks.push(*block);
+            *block = 0;
+        }
+        if data_blocks > DIRECT_BOUND {
+            blocks.push(self.indirect1);
+            let count = data_blocks.min(INDIRECT1_BOUND) - DIRECT_BOUND;
+            read_block(
+                device,
+                self.indirect1 as usize,
+                0,
+                |indirect: &IndirectBlock| blocks.extend_from_slice(&indirect[..count]),
+            );
+            self.indirect1 = 0;
+        }
+        if data_blocks > INDIRECT1_BOUND {
+            blocks.push(self.indirect2);
+            let rest = data_blocks - INDIRECT1_BOUND;
+            let indirect1_count = (rest + INDIRECT_COUNT - 1) / INDIRECT_COUNT;
+            let indirect2: IndirectBlock = read_block(
+                device,
+                self.indirect2 as usize,
+                0,
+                |indirect: &IndirectBlock| *indirect,
+            );
+            for (i, &indirect1) in indirect2.iter().take(indirect1_count).enumerate() {
+                blocks.push(indirect1);
+                let count = (rest - i * INDIRECT_COUNT).min(INDIRECT_COUNT);
+                read_block(device, indirect1 as usize, 0, |indirect: &IndirectBlock| {
+                    blocks.extend_from_slice(&indirect[..count])
+                });
+            }
+            self.indirect2 = 0;
+        }
+        self.size = 0;
+        blocks
+    }
+
+    /// read from `offset`, up to the end of the file, return the number of bytes read
+    pub fn read_at(&self, offset: usize, buf: &mut [u8], device: &dyn BlockDevice) -> usize {
+        let end = offset.saturating_add(buf.len()).min(self.size as usize);
+        let mut start = offset;
+        let mut read_size = 0;
+        while start < end {
+            let block_end = ((start / BLOCK_SZ + 1) * BLOCK_SZ).min(end);
+            let size = block_end - start;
+            read_block(
+                device,
+                self.get_block_id(start / BLOCK_SZ, device) as usize,
+                0,
+                |data: &DataBlock| {
+                    let block_offset = start % BLOCK_SZ;
+                    buf[read_size..read_size + size]
+                        .copy_from_slice(&data[block_offset..block_offset + size]);
+                },
+            );
+            read_size += size;
+            start = block_end;
+        }
+        read_size
+    }
+
+    /// Write at `offset`, the file must already be large enough.
+    pub fn write_at(&mut self, offset: usize, buf: &[u8], device: &dyn BlockDevice) -> usize {
+        let end = offset.saturating_add(buf.len()).min(self.size as usize);
+        let mut start = offset;
+        let mut write_size = 0;
+        while start < end {
+            let block_end = ((start / BLOCK_SZ + 1) * BLOCK_SZ).min(end);
+            let size = block_end - start;
+            modify_block(
+                device,
+                self.get_block_id(start / BLOCK_SZ, device) as usize,
+                0,
+                |data: &mut DataBlock| {
+                    let block_offset = start % BLOCK_SZ;
+                    data[block_offset..block_offset + size]
+                        .copy_from_slice(&buf[write_size..write_size + size]);
+                },
+            );
+            write_size += size;
+            start = block_end;
+        }
+        write_size
+    }
+}
+
+/// size of a directory entry, a directory is an array of them
+pub const DIRENT_SZ: usize = 32;
+
+#[repr(C)]
+pub struct DirEntry {
+    name: [u8; NAME_LENGTH_LIMIT + 1],
+    inode_number: u32,
+}
+
+impl DirEntry {
+    pub fn empty() -> Self {
+        Self {
+            name: [0; NAME_LENGTH_LIMIT + 1],
+            inode_number: 0,
+        }
+    }
+
+    /// `name` must not be longer than `NAME_LENGTH_LIMIT`
+    pub fn new(name: &str, inode_number: u32) -> Self {
+        let mut entry = Self::empty();
+        entry.name[..name.len()].copy_from_slice(name.as_bytes());
+        entry.inode_number = inode_number;
+        entry
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        unsafe { core::slice::from_raw_parts(self as *const _ as *const u8, DIRENT_SZ) }
+    }
+
+    pub fn as_bytes_mut(&mut self) -> &mut [u8] {
+        unsafe { core::slice::from_raw_parts_mut(self as *mut _ as *mut u8, DIRENT_SZ) }
+    }
+
+    pub fn name(&self) -> &str {
+        let len = self
+            .name
+            .iter()
+            .position(|&b| b == 0)
+            .unwrap_or(self.name.len());
+        core::str::from_utf8(&self.name[..len]).unwrap_or("")
+    }
+
+    pub fn inode_number(&self) -> u32 {
+        self.inode_number
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block_dev::MemDevice;
+
+    /// `count` distinct block ids, from 1 as 0 is never a data block
+    fn blocks(count: usize) -> Vec<u32> {
+        (1..=count as u32).collect()
+    }
+
+    fn new_inode() -> DiskInode {
+        let mut inode = DiskInode {
+            size: 0,
+            type_: 0,
+            direct: [0; INODE_DIRECT_COUNT],
+            indirect1: 0,
+            indirect2: 0,
+        };
+        inode.initialize(DiskInodeType::File);
+        inode
+    }
+
+    #[test]
+    fn total_blocks_counts_index_blocks() {
+        let size = |data_blocks: usize| (data_blocks * BLOCK_SZ) as u32;
+        assert_eq!(DiskInode::total_blocks(0), 0);
+        assert_eq!(DiskInode::total_blocks(1), 1);
+        assert_eq!(DiskInode::total_blocks(size(DIRECT_BOUND)), DIRECT_BOUND);
+        assert_eq!(
+            DiskInode::total_blocks(size(DIRECT_BOUND + 1)),
+            DIRECT_BOUND + 2
+        );
+        assert_eq!(
+            DiskInode::total_blocks(size(INDIRECT1_BOUND)),
+            INDIRECT1_BOUND + 1
+        );
+        assert_eq!(
+            DiskInode::total_blocks(size(INDIRECT1_BOUND + 1)),
+            INDIRECT1_BOUND + 4
+        );
+        assert_eq!(
+            DiskInode::total_blocks(size(INDIRECT1_BOUND + INDIRECT_COUNT + 1)),
+            INDIRECT1_BOUND + INDIRECT_COUNT + 5
+        );
+    }
+
+    #[test]
+    fn increase_then_clear_gives_every_block_back() {
+        let device = MemDevice::new(1024);
+        let mut inode = new_inode();
+        let size = ((INDIRECT1_BOUND + INDIRECT_COUNT + 3) * BLOCK_SZ) as u32;
+        let needed = inode.blocks_num_needed(size);
+        assert!(needed < 1024);
+        inode.increase_size(size, blocks(needed), device.as_ref());
+        assert_eq!(inode.data_blocks(), INDIRECT1_BOUND + INDIRECT_COUNT + 3);
+
+        let mut freed = inode.clear_size(device.as_ref());
+        freed.sort_unstable();
+        assert_eq!(freed, blocks(needed));
+        assert_eq!(inode.size, 0);
+    }
+
+    #[test]
+    fn data_blocks_are_distinct_across_index_levels() {
+        let device = MemDevice::new(1024);
+        let mut inode = new_inode();
+        let first = ((DIRECT_BOUND + 2) * BLOCK_SZ) as u32;
+        let needed = inode.blocks_num_needed(first);
+        inode.increase_size(first, blocks(needed), device.as_ref());
+        // grow again, the new blocks follow the ones already given
+        let second = ((INDIRECT1_BOUND + 2) * BLOCK_SZ) as u32;
+        let more = inode.blocks_num_needed(second);
+        let new_blocks = (needed as u32 + 1..=(needed + more) as u32).collect();
+        inode.increase_size(second, new_blocks, device.as_ref());
+
+        let mut ids: Vec<u32> = (0..inode.data_blocks())
+            .map(|i| inode.get_block_id(i, device.as_ref()))
+            .collect();
+        assert!(ids.iter().all(|&id| id != 0));
+        ids.sort_unstable();
+        ids.dedup();
+        assert_eq!(ids.len(), inode.data_blocks());
+    }
+
+    #[test]
+    fn read_and_write_stop_at_the_end_of_the_file() {
+        let device = MemDevice::new(64);
+        let mut inode = new_inode();
+        let needed = inode.blocks_num_needed(600);
+        inode.increase_size(600, blocks(needed), device.as_ref());
+        let data: Vec<u8> = (0..700).map(|i| i as u8).collect();
+        assert_eq!(inode.write_at(0, &data, device.as_ref()), 600);
+
+        let mut buf = [0u8; 200];
+        assert_eq!(inode.read_at(500, &mut buf, device.as_ref()), 100);
+        assert_eq!(buf[..100], data[500..600]);
+        assert_eq!(inode.read_at(600, &mut buf, device.as_ref()), 0);
+        assert_eq!(inode.read_at(usize::MAX, &mut buf, device.as_ref()), 0);
+    }
+
+    #[test]
+    fn dir_entry_keeps_its_name_and_inode() {
+        let name = "a".repeat(NAME_LENGTH_LIMIT);
+        let entry = DirEntry::new(&name, 42);
+        assert_eq!(entry.as_bytes().len(), DIRENT_SZ);
+        let mut copy = DirEntry::empty();
+        copy.as_bytes_mut().copy_from_slice(entry.as_bytes());
+        assert_eq!(copy.name(), name);
+        assert_eq!(copy.inode_number(), 42);
+    }
+}
@@ -0,0 +1,21 @@
+//! A simple inode based file system, shared by the kernel and the host side packer
+//!
+//! Disk layout, in blocks of [`BLOCK_SZ`] bytes:
+//! super block | inode bitmap | inode area | data bitmap | data area
+#![cfg_attr(not(test), no_std)]
+
+extern crate alloc;
+
+mod bitmap;
+mod block_dev;
+mod layout;
+mod sfs;
+mod vfs;
+
+pub use block_dev::BlockDevice;
+pub use layout::{DiskInodeType, NAME_LENGTH_LIMIT};
+pub use sfs::SimpleFileSystem;
+pub use vfs::Inode;
+
+/// size of a block, the same as a sector of the device
+pub const BLOCK_SZ: usize = 512;
@@ -0,0 +1,190 @@
+use crate::bitmap::Bitmap;
+use crate::block_dev::{modify_block, read_block, BlockDevice};
+use crate::layout::{DiskInode, DiskInodeType, SuperBlock};
+use crate::vfs::Inode;
+use crate::BLOCK_SZ;
+use alloc::sync::Arc;
+use core::mem::size_of;
+use spin::Mutex;
+
+const INODES_PER_BLOCK: usize = BLOCK_SZ / size_of::<DiskInode>();
+const ROOT_INODE_ID: u32 = 0;
+
+pub struct SimpleFileSystem {
+    pub block_device: Arc<dyn BlockDevice>,
+    inode_bitmap: Bitmap,
+    data_bitmap: Bitmap,
+    inode_area_start_block: u32,
+    data_area_start_block: u32,
+}
+
+impl SimpleFileSystem {
+    /// Format the first `total_blocks` blocks of `block_device`,
+    /// with room for `inode_bitmap_blocks * BLOCK_SZ * 8` inodes.
+    pub fn create(
+        block_device: Arc<dyn BlockDevice>,
+        total_blocks: u32,
+        inode_bitmap_blocks: u32,
+    ) -> Arc<Mutex<Self>> {
+        let inode_bitmap = Bitmap::new(1, inode_bitmap_blocks as usize * BLOCK_SZ * 8);
+        let inode_area_blocks =
+            ((inode_bitmap.maximum() * size_of::<DiskInode>() + BLOCK_SZ - 1) / BLOCK_SZ) as u32;
+        let inode_total_blocks = inode_bitmap_blocks + inode_area_blocks;
+        let data_total_blocks = total_blocks - 1 - inode_total_blocks;
+        // every block of the data bitmap describes BLOCK_SZ * 8 data blocks
+        let data_bitmap_blocks =
+            (data_total_blocks + BLOCK_SZ as u32 * 8) / (BLOCK_SZ as u32 * 8 + 1);
+        let data_area_blocks = data_total_blocks - data_bitmap_blocks;
+        let data_bitmap = Bitmap::new((1 + inode_total_blocks) as usize, data_area_blocks as usize);
+        let fs = Self {
+            block_device,
+            inode_bitmap,
+            data_bitmap,
+            inode_area_start_block: 1 + inode_bitmap_blocks,
+            data_area_start_block: 1 + inode_total_blocks + data_bitmap_blocks,
+        };
+
+        let zero = [0u8; BLOCK_SZ];
+        for block_id in 0..total_blocks {
+            fs.block_device.write_block(block_id as usize, &zero);
+        }
+        modify_block(
+            fs.block_device.as_ref(),
+            0,
+            0,
+            |super_block: &mut SuperBlock| {
+                super_block.initialize(
+                    total_blocks,
+                    inode_bitmap_blocks,
+                    inode_area_blocks,
+                    data_bitmap_blocks,
+                    data_area_blocks,
+                );
+            },
+        );
+        assert_eq!(fs.alloc_inode(), Some(ROOT_INODE_ID));
+        let (block_id, offset) = fs.get_disk_inode_pos(ROOT_INODE_ID);
+        modify_block(
+            fs.block_device.as_ref(),
+            block_id as usize,
+            offset,
+            |disk_inode: &mut DiskInode| disk_inode.initialize(DiskInodeType::Directory),
+        );
+        Arc::new(Mutex::new(fs))
+    }
+
+    /// Return None if there is no file system on `block_device`.
+    pub fn open(block_device: Arc<dyn BlockDevice>) -> Option<Arc<Mutex<Self>>> {
+        read_block(block_device.as_ref(), 0, 0, |super_block: &SuperBlock| {
+            if !super_block.is_valid() {
+                return None;
+            }
+            let inode_total_blocks =
+                super_block.inode_bitmap_blocks + super_block.inode_area_blocks;
+            let fs = Self {
+                block_device: block_device.clone(),
+                inode_bitmap: Bitmap::new(
+                    1,
+                    super_block.inode_bitmap_blocks as usize * BLOCK_SZ * 8,
+                ),
+                data_bitmap: Bitmap::new(
+                    (1 + inode_total_blocks) as usize,
+                    super_block.data_area_blocks as usize,
+                ),
+                inode_area_start_block: 1 + super_block.inode_bitmap_blocks,
+                data_area_start_block: 1 + inode_total_blocks + super_block.data_bitmap_blocks,
+            };
+            Some(Arc::new(Mutex::new(fs)))
+        })
+    }
+
+    pub fn root_inode(fs: &Arc<Mutex<Self>>) -> Inode {
+        let block_device = fs.lock().block_device.clone();
+        Inode::new(ROOT_INODE_ID, fs.clone(), block_device)
+    }
+
+    /// block id and offset in it of the disk inode `inode_id`
+    pub fn get_disk_inode_pos(&self, inode_id: u32) -> (u32, usize) {
+        let inode_id = inode_id as usize;
+        let block_id = self.inode_area_start_block as usize + inode_id / INODES_PER_BLOCK;
+        let offset = (inode_id % INODES_PER_BLOCK) * size_of::<DiskInode>();
+        (block_id as u32, offset)
+    }
+
+    pub fn alloc_inode(&self) -> Option<u32> {
+        self.inode_bitmap
+            .alloc(self.block_device.as_ref())
+            .map(|id| id as u32)
+    }
+
+    /// allocate a data block, zeroed, and return its block id
+    pub fn alloc_data(&self) -> Option<u32> {
+        let bit = self.data_bitmap.alloc(self.block_device.as_ref())?;
+        let block_id = self.data_area_start_block + bit as u32;
+        self.block_device
+            .write_block(block_id as usize, &[0u8; BLOCK_SZ]);
+        Some(block_id)
+    }
+
+    pub fn dealloc_data(&self, block_id: u32) {
+        self.data_bitmap.dealloc(
+            self.block_device.as_ref(),
+            (block_id - self.data_area_start_block) as usize,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block_dev::MemDevice;
+
+    #[test]
+    fn layout_fits_in_the_device() {
+        for total_blocks in [1028u32, 2048, 5123, 5124, 32768] {
+            let device = MemDevice::new(total_blocks as usize);
+            let fs = SimpleFileSystem::create(device.clone(), total_blocks, 1);
+            let fs = fs.lock();
+            let data_end = fs.data_area_start_block as usize + fs.data_bitmap.maximum();
+            assert!(data_end <= total_blocks as usize);
+            // the data bitmap has a bit for every data block
+            let data_bitmap_blocks =
+                fs.data_area_start_block as usize - fs.data_bitmap.start_block_id();
+            assert!(data_bitmap_blocks * BLOCK_SZ * 8 >= fs.data_bitmap.maximum());
+        }
+    }
+
+    #[test]
+    fn open_finds_what_create_wrote() {
+        let device = MemDevice::new(2048);
+        assert!(SimpleFileSystem::open(device.clone()).is_none());
+        let fs = SimpleFileSystem::create(device.clone(), 2048, 1);
+        let root = SimpleFileSystem::root_inode(&fs);
+        root.create("file", DiskInodeType::File)
+            .unwrap()
+            .write_at(0, b"hello");
+
+        let fs = SimpleFileSystem::open(device).unwrap();
+        let root = SimpleFileSystem::root_inode(&fs);
+        assert!(root.is_dir());
+        let file = root.find("file").unwrap();
+        let mut buf = [0u8; 8];
+        assert_eq!(file.read_at(0, &mut buf), 5);
+        assert_eq!(&buf[..5], b"hello");
+    }
+
+    #[test]
+    fn data_blocks_are_zeroed_and_given_back() {
+        let device = MemDevice::new(2048);
+        let fs = SimpleFileSystem::create(device.clone(), 2048, 1);
+        let fs = fs.lock();
+        let block_id = fs.alloc_data().unwrap();
+        assert!(block_id >= fs.data_area_start_block);
+        device.write_block(block_id as usize, &[0xff; BLOCK_SZ]);
+        fs.dealloc_data(block_id);
+        assert_eq!(fs.alloc_data(), Some(block_id));
+        let mut block = [0xffu8; BLOCK_SZ];
+        device.read_block(block_id as usize, &mut block);
+        assert!(block.iter().all(|&byte| byte == 0));
+    }
+}
@@ -0,0 +1,337 @@
+use crate::block_dev::{modify_block, read_block, BlockDevice};
+use crate::layout::{DirEntry, DiskInode, DiskInodeType, DIRENT_SZ, NAME_LENGTH_LIMIT};
+use crate::sfs::SimpleFileSystem;
+use alloc::string::{String, ToString};
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use spin::{Mutex, MutexGuard};
+
+/// Handle of a file or directory, every operation locks the whole file system
+pub struct Inode {
+    inode_id: u32,
+    block_id: usize,
+    block_offset: usize,
+    fs: Arc<Mutex<SimpleFileSystem>>,
+    block_device: Arc<dyn BlockDevice>,
+}
+
+impl Inode {
+    pub(crate) fn new(
+        inode_id: u32,
+        fs: Arc<Mutex<SimpleFileSystem>>,
+        block_device: Arc<dyn BlockDevice>,
+    ) -> Self {
+        let (block_id, block_offset) = fs.lock().get_disk_inode_pos(inode_id);
+        Self {
+            inode_id,
+            block_id: block_id as usize,
+            block_offset,
+            fs,
+            block_device,
+        }
+    }
+
+    fn read_disk_inode<V>(&self, f: impl FnOnce(&DiskInode) -> V) -> V {
+        read_block(
+            self.block_device.as_ref(),
+            self.block_id,
+            self.block_offset,
+            f,
+        )
+    }
+
+    fn modify_disk_inode<V>(&self, f: impl FnOnce(&mut DiskInode) -> V) -> V {
+        modify_block(
+            self.block_device.as_ref(),
+            self.block_id,
+            self.block_offset,
+            f,
+        )
+    }
+
+    pub fn inode_id(&self) -> u32 {
+        self.inode_id
+    }
+
+    pub fn size(&self) -> usize {
+        let _fs = self.fs.lock();
+        self.read_disk_inode(|disk_inode| disk_inode.size as usize)
+    }
+
+    pub fn is_dir(&self) -> bool {
+        let _fs = self.fs.lock();
+        self.read_disk_inode(|disk_inode| disk_inode.is_dir())
+    }
+
+    fn dir_entries(&self, disk_inode: &DiskInode) -> Vec<DirEntry> {
+        assert!(disk_inode.is_dir());
+        let count = disk_inode.size as usize / DIRENT_SZ;
+        (0..count)
+            .map(|i| {
+                let mut entry = DirEntry::empty();
+                let read_size = disk_inode.read_at(
+                    i * DIRENT_SZ,
+                    entry.as_bytes_mut(),
+                    self.block_device.as_ref(),
+                );
+                assert_eq!(read_size, DIRENT_SZ);
+                entry
+            })
+            .collect()
+    }
+
+    fn find_inode_id(&self, name: &str, disk_inode: &DiskInode) -> Option<u32> {
+        self.dir_entries(disk_inode)
+            .iter()
+            .find(|entry| entry.name() == name)
+            .map(|entry| entry.inode_number())
+    }
+
+    /// child `name` of this directory, None if this is not a directory
+    pub fn find(&self, name: &str) -> Option<Arc<Inode>> {
+        let fs = self.fs.lock();
+        let inode_id = self.read_disk_inode(|disk_inode| {
+            if !disk_inode.is_dir() {
+                return None;
+            }
+            self.find_inode_id(name, disk_inode)
+        })?;
+        drop(fs);
+        Some(Arc::new(Self::new(
+            inode_id,
+            self.fs.clone(),
+            self.block_device.clone(),
+        )))
+    }
+
+    /// grow `disk_inode` to `new_size`, return false if the disk is full
+    fn increase_size(
+        &self,
+        new_size: u32,
+        disk_inode: &mut DiskInode,
+        fs: &mut MutexGuard<SimpleFileSystem>,
+    ) -> bool {
+        if new_size <= disk_inode.size {
+            return true;
+        }
+        if new_size as usize > DiskInode::max_size() {
+            return false;
+        }
+        let mut new_blocks = Vec::new();
+        for _ in 0..disk_inode.blocks_num_needed(new_size) {
+            match fs.alloc_data() {
+                Some(block_id) => new_blocks.push(block_id),
+                None => {
+                    for block_id in new_blocks {
+                        fs.dealloc_data(block_id);
+                    }
+                    return false;
+                }
+            }
+        }
+        disk_inode.increase_size(new_size, new_blocks, self.block_device.as_ref());
+        true
+    }
+
+    /// Create `name` in this directory.
+    /// Return None if this is not a directory, the name is taken or invalid, or the disk is full.
+    pub fn create(&self, name: &str, type_: DiskInodeType) -> Option<Arc<Inode>> {
+        if name.is_empty() || name.len() > NAME_LENGTH_LIMIT || name.contains('/') {
+            return None;
+        }
+        let mut fs = self.fs.lock();
+        let exists = self.read_disk_inode(|disk_inode| {
+            !disk_inode.is_dir() || self.find_inode_id(name, disk_inode).is_some()
+        });
+        if exists {
+            return None;
+        }
+        let inode_id = fs.alloc_inode()?;
+        let (block_id, block_offset) = fs.get_disk_inode_pos(inode_id);
+        modify_block(
+            self.block_device.as_ref(),
+            block_id as usize,
+            block_offset,
+            |disk_inode: &mut DiskInode| disk_inode.initialize(type_),
+        );
+        // the new inode may share a block with this one, so it is read again
+        let added = self.modify_disk_inode(|disk_inode| {
+            let offset = disk_inode.size as usize;
+            if !self.increase_size((offset + DIRENT_SZ) as u32, disk_inode, &mut fs) {
+                return false;
+            }
+            let entry = DirEntry::new(name, inode_id);
+            disk_inode.write_at(offset, entry.as_bytes(), self.block_device.as_ref());
+            true
+        });
+        if !added {
+            return None;
+        }
+        drop(fs);
+        Some(Arc::new(Self::new(
+            inode_id,
+            self.fs.clone(),
+            self.block_device.clone(),
+        )))
+    }
+
+    /// names of the entries of this directory, empty if this is not a directory
+    pub fn ls(&self) -> Vec<String> {
+        let _fs = self.fs.lock();
+        self.read_disk_inode(|disk_inode| {
+            if !disk_inode.is_dir() {
+                return Vec::new();
+            }
+            self.dir_entries(disk_inode)
+                .iter()
+                .map(|entry| entry.name().to_string())
+                .collect()
+        })
+    }
+
+    pub fn read_at(&self, offset: usize, buf: &mut [u8]) -> usize {
+        let _fs = self.fs.lock();
+        self.read_disk_inode(|disk_inode| {
+            disk_inode.read_at(offset, buf, self.block_device.as_ref())
+        })
+    }
+
+    /// write at `offset`, growing the file if needed, return the number of bytes written.
+    /// Nothing is written if the file would grow past `DiskInode::max_size`.
+    pub fn write_at(&self, offset: usize, buf: &[u8]) -> usize {
+        // the size is stored on 32 bits, a larger end must not wrap around
+        let end = match offset.checked_add(buf.len()) {
+            Some(end) if end <= DiskInode::max_size() => end,
+            _ => return 0,
+        };
+        let mut fs = self.fs.lock();
+        self.modify_disk_inode(|disk_inode| {
+            if end > disk_inode.size as usize
+                && !self.increase_size(end as u32, disk_inode, &mut fs)
+            {
+                return 0;
+            }
+            disk_inode.write_at(offset, buf, self.block_device.as_ref())
+        })
+    }
+
+    /// drop the content of the file and free its blocks
+    pub fn clear(&self) {
+        let fs = self.fs.lock();
+        self.modify_disk_inode(|disk_inode| {
+            for block_id in disk_inode.clear_size(self.block_device.as_ref()) {
+                fs.dealloc_data(block_id);
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block_dev::MemDevice;
+    use crate::BLOCK_SZ;
+
+    /// super block, a block of inode bitmap, its inode area and a block of data bitmap
+    const META_BLOCKS: usize = 1 + 1 + 1024 + 1;
+
+    /// a file system with `data_blocks` data blocks
+    fn new_fs(data_blocks: usize) -> Inode {
+        let total_blocks = META_BLOCKS + data_blocks;
+        let device = MemDevice::new(total_blocks);
+        let fs = SimpleFileSystem::create(device, total_blocks as u32, 1);
+        SimpleFileSystem::root_inode(&fs)
+    }
+
+    #[test]
+    fn create_find_and_list() {
+        let root = new_fs(256);
+        let file = root.create("file", DiskInodeType::File).unwrap();
+        let dir = root.create("dir", DiskInodeType::Directory).unwrap();
+        assert!(!file.is_dir());
+        assert!(dir.is_dir());
+        assert_ne!(file.inode_id(), dir.inode_id());
+        assert_eq!(root.find("file").unwrap().inode_id(), file.inode_id());
+        assert!(root.find("missing").is_none());
+        assert_eq!(root.ls(), ["file", "dir"]);
+        // a file has no entries
+        assert!(file.find("file").is_none());
+        assert!(file.create("nested", DiskInodeType::File).is_none());
+        assert!(file.ls().is_empty());
+    }
+
+    #[test]
+    fn create_rejects_bad_or_taken_names() {
+        let root = new_fs(256);
+        assert!(root.create("name", DiskInodeType::File).is_some());
+        assert!(root.create("name", DiskInodeType::Directory).is_none());
+        assert!(root.create("", DiskInodeType::File).is_none());
+        assert!(root.create("a/b", DiskInodeType::File).is_none());
+        let longest = "n".repeat(NAME_LENGTH_LIMIT);
+        assert!(root.create(&longest, DiskInodeType::File).is_some());
+        let too_long = "n".repeat(NAME_LENGTH_LIMIT + 1);
+        assert!(root.create(&too_long, DiskInodeType::File).is_none());
+        assert_eq!(root.ls().len(), 2);
+    }
+
+    #[test]
+    fn write_and_read_across_index_blocks() {
+        let root = new_fs(1024);
+        let file = root.create("file", DiskInodeType::File).unwrap();
+        // past the direct blocks and into the first indirect block
+        let data: Vec<u8> = (0..40 * BLOCK_SZ).map(|i| (i * 7) as u8).collect();
+        assert_eq!(file.write_at(0, &data), data.len());
+        assert_eq!(file.size(), data.len());
+        let mut buf = vec![0u8; data.len()];
+        assert_eq!(file.read_at(0, &mut buf), data.len());
+        assert_eq!(buf, data);
+
+        // writing past the end leaves a hole of zeros
+        let end = data.len() + 3 * BLOCK_SZ;
+        assert_eq!(file.write_at(end, b"tail"), 4);
+        assert_eq!(file.size(), end + 4);
+        let mut hole = vec![0xffu8; 3 * BLOCK_SZ];
+        assert_eq!(file.read_at(data.len(), &mut hole), hole.len());
+        assert!(hole.iter().all(|&byte| byte == 0));
+    }
+
+    #[test]
+    fn write_past_the_largest_size_writes_nothing() {
+        let root = new_fs(256);
+        let file = root.create("file", DiskInodeType::File).unwrap();
+        // the end does not fit in the 32 bit size and must not wrap around to a small one
+        let offset = u32::MAX as usize + 1;
+        assert_eq!(file.write_at(offset, b"data"), 0);
+        assert_eq!(file.write_at(DiskInode::max_size(), b"d"), 0);
+        assert_eq!(file.write_at(usize::MAX, b"d"), 0);
+        assert_eq!(file.size(), 0);
+    }
+
+    #[test]
+    fn full_disk_keeps_no_block_of_a_failed_write() {
+        let root = new_fs(128);
+        let file = root.create("file", DiskInodeType::File).unwrap();
+        let huge = vec![1u8; 256 * BLOCK_SZ];
+        assert_eq!(file.write_at(0, &huge), 0);
+        assert_eq!(file.size(), 0);
+        // every block is still free, so a file filling most of the disk fits
+        let data = vec![2u8; 64 * BLOCK_SZ];
+        assert_eq!(file.write_at(0, &data), data.len());
+    }
+
+    #[test]
+    fn clear_frees_the_blocks() {
+        let root = new_fs(128);
+        let first = root.create("first", DiskInodeType::File).unwrap();
+        let data = vec![3u8; 64 * BLOCK_SZ];
+        assert_eq!(first.write_at(0, &data), data.len());
+        let second = root.create("second", DiskInodeType::File).unwrap();
+        assert_eq!(second.write_at(0, &data), 0);
+
+        first.clear();
+        assert_eq!(first.size(), 0);
+        assert_eq!(second.write_at(0, &data), data.len());
+        let mut buf = [0u8; 4];
+        assert_eq!(first.read_at(0, &mut buf), 0);
+    }
+}
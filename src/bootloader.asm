@@ -1,6 +1,7 @@
     .section .text.entry
     .globl _start
 _start:
+    # a0 = hart id, a1 = device tree blob, both left untouched for rust_main
     la sp, eboot_stack
     j rust_main
 
@@ -11,32 +12,11 @@ sboot_stack:
     .globl eboot_stack
 eboot_stack:
 
-    .align 4
-    .section .data
-    .global _num_app
-_num_app: 
-    .quad 2
-    .quad _app0_start
-    .quad _app1_start
-    .quad _app1_end
-    
-    .global _app_names
-_app_names:
-    .string "initproc"
-    .string "console_out"
-
-    .section .data
-    .global _app0_start
-    .global _app0_end
-    .align 4
-_app0_start:
-    .incbin "../prototype_lib/target/riscv64gc-unknown-none-elf/release/initproc"
-_app0_end:
-
+    # the initramfs used when the boot loader passes none, built by `make initramfs`
     .section .data
-    .global _app1_start
-    .global _app1_end
+    .global _initramfs_start
+    .global _initramfs_end
     .align 4
-_app1_start:
-    .incbin "../prototype_lib/target/riscv64gc-unknown-none-elf/release/console_out"
-_app1_end:
+_initramfs_start:
+    .incbin "target/initramfs.cpio"
+_initramfs_end:
@@ -0,0 +1,214 @@
+//! Write-back block cache shared by all block devices
+//!
+//! Blocks are cached in a fixed number of frames and keyed by (device id, block id),
+//! the least recently used one is evicted, and written back first if it is dirty.
+use super::{BlockDevice, BLOCK_DEVICES, BLOCK_SIZE};
+use crate::hal::*;
+use crate::mm::page_table::frame::{frame_alloc, FrameTracker};
+use crate::sync::upsafecell::UPSafeCell;
+use crate::sysconfig::{BLOCK_CACHE_FRAMES, PAGE_SIZE};
+use alloc::collections::BTreeMap;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use lazy_static::*;
+
+const BLOCKS_PER_FRAME: usize = PAGE_SIZE / BLOCK_SIZE;
+
+/// counters to tune `BLOCK_CACHE_FRAMES` with
+#[derive(Copy, Clone, Debug, Default)]
+pub struct BlockCacheStats {
+    pub hits: usize,
+    pub misses: usize,
+    /// dirty blocks written to their device, on eviction or sync
+    pub write_backs: usize,
+}
+
+struct Slot {
+    /// (device id, block id) of the cached block, None if the slot is free
+    key: Option<(usize, usize)>,
+    device: Option<Arc<dyn BlockDevice>>,
+    dirty: bool,
+    /// tick of the last access
+    last_used: usize,
+}
+
+pub struct BlockCache {
+    frames: Vec<FrameTracker>,
+    slots: Vec<Slot>,
+    /// slot of every cached block
+    map: BTreeMap<(usize, usize), usize>,
+    tick: usize,
+    stats: BlockCacheStats,
+}
+
+impl BlockCache {
+    fn new() -> Self {
+        let frames: Vec<FrameTracker> = (0..BLOCK_CACHE_FRAMES)
+            .map(|_| frame_alloc().expect("No frame for the block cache!"))
+            .collect();
+        let slots = (0..BLOCK_CACHE_FRAMES * BLOCKS_PER_FRAME)
+            .map(|_| Slot {
+                key: None,
+                device: None,
+                dirty: false,
+                last_used: 0,
+            })
+            .collect();
+        Self {
+            frames,
+            slots,
+            map: BTreeMap::new(),
+            tick: 0,
+            stats: BlockCacheStats::default(),
+        }
+    }
+
+    fn slot_data(&self, slot: usize) -> &'static mut [u8] {
+        let offset = (slot % BLOCKS_PER_FRAME) * BLOCK_SIZE;
+        &mut self.frames[slot / BLOCKS_PER_FRAME]
+            .ppn
+            .get_bytes_array_mut()[offset..offset + BLOCK_SIZE]
+    }
+
+    fn write_back(&mut self, slot: usize) {
+        if !self.slots[slot].dirty {
+            return;
+        }
+        let (_, block_id) = self.slots[slot].key.unwrap();
+        let device = self.slots[slot].device.clone().unwrap();
+        device.write_block(block_id, self.slot_data(slot));
+        self.slots[slot].dirty = false;
+        self.stats.write_backs += 1;
+    }
+
+    /// Return the slot of the block, loading it from the device on a miss
+    /// unless the caller is about to overwrite all of it.
+    fn get_slot(
+        &mut self,
+        device_id: usize,
+        device: &Arc<dyn BlockDevice>,
+        block_id: usize,
+        overwrite: bool,
+    ) -> usize {
+        self.tick += 1;
+        let key = (device_id, block_id);
+        if let Some(&slot) = self.map.get(&key) {
+            self.stats.hits += 1;
+            self.slots[slot].last_used = self.tick;
+            return slot;
+        }
+        self.stats.misses += 1;
+
+        let slot = (0..self.slots.len())
+            .min_by_key(|&slot| match self.slots[slot].key {
+                None => 0,
+                Some(_) => self.slots[slot].last_used,
+            })
+            .unwrap();
+        self.write_back(slot);
+        if let Some(old_key) = self.slots[slot].key.take() {
+            self.map.remove(&old_key);
+        }
+        if !overwrite {
+            device.read_block(block_id, self.slot_data(slot));
+        }
+        self.slots[slot] = Slot {
+            key: Some(key),
+            device: Some(device.clone()),
+            dirty: false,
+            last_used: self.tick,
+        };
+        self.map.insert(key, slot);
+        slot
+    }
+
+    pub fn read(
+        &mut self,
+        device_id: usize,
+        device: &Arc<dyn BlockDevice>,
+        block_id: usize,
+        buf: &mut [u8],
+    ) {
+        let slot = self.get_slot(device_id, device, block_id, false);
+        buf.copy_from_slice(self.slot_data(slot));
+    }
+
+    pub fn write(
+        &mut self,
+        device_id: usize,
+        device: &Arc<dyn BlockDevice>,
+        block_id: usize,
+        buf: &[u8],
+    ) {
+        let slot = self.get_slot(device_id, device, block_id, true);
+        self.slot_data(slot).copy_from_slice(buf);
+        self.slots[slot].dirty = true;
+    }
+
+    /// write back every dirty block of `device_id`, or of all devices if None
+    pub fn sync(&mut self, device_id: Option<usize>) {
+        for slot in 0..self.slots.len() {
+            match (self.slots[slot].key, device_id) {
+                (Some(_), None) => self.write_back(slot),
+                (Some((id, _)), Some(device_id)) if id == device_id => self.write_back(slot),
+                _ => {}
+            }
+        }
+    }
+}
+
+lazy_static! {
+    pub static ref BLOCK_CACHE: UPSafeCell<BlockCache> =
+        unsafe { UPSafeCell::new(BlockCache::new()) };
+}
+
+/// A block device whose blocks go through the block cache
+pub struct CachedBlockDevice {
+    device_id: usize,
+    device: Arc<dyn BlockDevice>,
+}
+
+impl BlockDevice for CachedBlockDevice {
+    fn read_block(&self, block_id: usize, buf: &mut [u8]) {
+        assert_eq!(buf.len(), BLOCK_SIZE);
+        BLOCK_CACHE
+            .exclusive_access()
+            .read(self.device_id, &self.device, block_id, buf);
+    }
+
+    fn write_block(&self, block_id: usize, buf: &[u8]) {
+        assert_eq!(buf.len(), BLOCK_SIZE);
+        BLOCK_CACHE
+            .exclusive_access()
+            .write(self.device_id, &self.device, block_id, buf);
+    }
+
+    fn block_count(&self) -> usize {
+        self.device.block_count()
+    }
+}
+
+impl CachedBlockDevice {
+    /// write back the dirty blocks of this device
+    pub fn sync(&self) {
+        BLOCK_CACHE.exclusive_access().sync(Some(self.device_id));
+    }
+}
+
+/// block device `index` of `BLOCK_DEVICES`, through the cache
+pub fn cached_block_device(index: usize) -> Option<Arc<CachedBlockDevice>> {
+    let device = BLOCK_DEVICES.exclusive_access().get(index)?.clone();
+    Some(Arc::new(CachedBlockDevice {
+        device_id: index,
+        device,
+    }))
+}
+
+/// write back every dirty block
+pub fn sync_all() {
+    BLOCK_CACHE.exclusive_access().sync(None);
+}
+
+pub fn block_cache_stats() -> BlockCacheStats {
+    BLOCK_CACHE.exclusive_access().stats
+}
@@ -0,0 +1,86 @@
+//! Block devices, addressed in 512 bytes sectors
+pub mod cache;
+pub mod virtio_blk;
+
+use crate::sync::upsafecell::UPSafeCell;
+use alloc::format;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::any::Any;
+use lazy_static::*;
+
+pub use cache::{block_cache_stats, cached_block_device, sync_all, CachedBlockDevice};
+pub use virtio_blk::VirtIOBlk;
+
+/// size of a sector, the unit of every block device
+pub const BLOCK_SIZE: usize = 512;
+
+pub trait BlockDevice: Send + Sync + Any {
+    /// read sector `block_id` into `buf`, which holds exactly one sector
+    fn read_block(&self, block_id: usize, buf: &mut [u8]);
+    /// write `buf`, which holds exactly one sector, to sector `block_id`
+    fn write_block(&self, block_id: usize, buf: &[u8]);
+    /// number of sectors of the device
+    fn block_count(&self) -> usize;
+    /// called when the interrupt line of the device is raised
+    fn handle_irq(&self) {}
+}
+
+lazy_static! {
+    /// block devices found at boot, in device tree order
+    pub static ref BLOCK_DEVICES: UPSafeCell<Vec<Arc<dyn BlockDevice>>> =
+        unsafe { UPSafeCell::new(Vec::new()) };
+}
+
+pub fn register_block_device(device: Arc<dyn BlockDevice>) {
+    BLOCK_DEVICES.exclusive_access().push(device);
+}
+
+pub fn block_device(index: usize) -> Option<Arc<dyn BlockDevice>> {
+    BLOCK_DEVICES.exclusive_access().get(index).cloned()
+}
+
+/// name of the block device `index` in devfs: vda, vdb, ...
+pub fn block_device_name(index: usize) -> String {
+    format!("vd{}", (b'a' + index as u8) as char)
+}
+
+/// read `buf.len()` bytes from byte `offset` of `device`, which need not be block aligned
+pub fn read_bytes(device: &dyn BlockDevice, offset: usize, buf: &mut [u8]) {
+    let mut block = [0u8; BLOCK_SIZE];
+    let mut done = 0;
+    while done < buf.len() {
+        let pos = offset + done;
+        let block_offset = pos % BLOCK_SIZE;
+        let size = core::cmp::min(BLOCK_SIZE - block_offset, buf.len() - done);
+        device.read_block(pos / BLOCK_SIZE, &mut block);
+        buf[done..done + size].copy_from_slice(&block[block_offset..block_offset + size]);
+        done += size;
+    }
+}
+
+/// write `buf` at byte `offset` of `device`, partial blocks are read first
+pub fn write_bytes(device: &dyn BlockDevice, offset: usize, buf: &[u8]) {
+    let mut block = [0u8; BLOCK_SIZE];
+    let mut done = 0;
+    while done < buf.len() {
+        let pos = offset + done;
+        let block_offset = pos % BLOCK_SIZE;
+        let size = core::cmp::min(BLOCK_SIZE - block_offset, buf.len() - done);
+        if size < BLOCK_SIZE {
+            device.read_block(pos / BLOCK_SIZE, &mut block);
+        }
+        block[block_offset..block_offset + size].copy_from_slice(&buf[done..done + size]);
+        device.write_block(pos / BLOCK_SIZE, &block);
+        done += size;
+    }
+}
+
+/// external interrupt handler shared by all block devices, each one checks its own status
+pub fn handle_irq() {
+    let devices = BLOCK_DEVICES.exclusive_access().clone();
+    for device in devices {
+        device.handle_irq();
+    }
+}
@@ -0,0 +1,153 @@
+//! virtio-blk driver, one request in flight per caller
+//!
+//! The caller waits for its request either by polling the used ring, or, when the device has
+//! an interrupt line, by sleeping until the PLIC raises an external interrupt.
+use super::{BlockDevice, BLOCK_SIZE};
+use crate::drivers::virtio::queue::{VirtQueue, QUEUE_SIZE};
+use crate::drivers::virtio::VirtIOMmio;
+use crate::hal::*;
+use crate::mm::page_table::frame::frame_alloc;
+use crate::sync::upsafecell::UPSafeCell;
+
+const REQUEST_IN: u32 = 0;
+const REQUEST_OUT: u32 = 1;
+const STATUS_OK: u8 = 0;
+
+/// capacity in sectors, offset in the configuration space
+const CONFIG_CAPACITY: usize = 0;
+
+/// layout of the frame that holds a request, the caller's buffer may not be identity mapped
+const HEADER_OFFSET: usize = 0;
+const HEADER_SIZE: usize = 16;
+const STATUS_OFFSET: usize = HEADER_SIZE;
+const DATA_OFFSET: usize = BLOCK_SIZE;
+
+pub struct VirtIOBlk {
+    inner: UPSafeCell<VirtIOBlkInner>,
+    /// interrupt source on the PLIC, None if the device is polled
+    irq: Option<usize>,
+    capacity: usize,
+}
+
+struct VirtIOBlkInner {
+    mmio: VirtIOMmio,
+    queue: VirtQueue,
+    /// requests used by the device but not yet collected by their caller
+    done: [bool; QUEUE_SIZE],
+}
+
+impl VirtIOBlkInner {
+    fn collect_used(&mut self) {
+        while let Some((head, _)) = self.queue.pop_used() {
+            self.done[head as usize] = true;
+        }
+    }
+}
+
+impl VirtIOBlk {
+    /// Return None if the device can not be set up.
+    /// With an `irq`, the caller still has to register it on the PLIC.
+    pub fn new(mmio: VirtIOMmio, irq: Option<usize>) -> Option<Self> {
+        mmio.init(0)?;
+        let mut queue = VirtQueue::new();
+        queue.set_interrupt(irq.is_some());
+        if !mmio.setup_queue(0, &queue) {
+            return None;
+        }
+        mmio.driver_ok();
+        let capacity = mmio.config_u64(CONFIG_CAPACITY) as usize;
+        Some(Self {
+            inner: unsafe {
+                UPSafeCell::new(VirtIOBlkInner {
+                    mmio,
+                    queue,
+                    done: [false; QUEUE_SIZE],
+                })
+            },
+            irq,
+            capacity,
+        })
+    }
+
+    /// Submit one request and wait for it, `data` is read from or written to the sector.
+    fn request(&self, request_type: u32, block_id: usize, data: &mut [u8]) {
+        assert_eq!(data.len(), BLOCK_SIZE);
+        assert!(block_id < self.capacity, "sector {} out of range", block_id);
+        let frame = frame_alloc().unwrap();
+        let bytes = frame.ppn.get_bytes_array_mut();
+        bytes[HEADER_OFFSET..HEADER_OFFSET + 4].copy_from_slice(&request_type.to_le_bytes());
+        bytes[HEADER_OFFSET + 8..HEADER_OFFSET + 16]
+            .copy_from_slice(&(block_id as u64).to_le_bytes());
+        bytes[STATUS_OFFSET] = u8::MAX;
+        if request_type == REQUEST_OUT {
+            bytes[DATA_OFFSET..DATA_OFFSET + BLOCK_SIZE].copy_from_slice(data);
+        }
+
+        let base = bytes.as_ptr() as usize;
+        let header = (base + HEADER_OFFSET, HEADER_SIZE);
+        let buffer = (base + DATA_OFFSET, BLOCK_SIZE);
+        let status = (base + STATUS_OFFSET, 1);
+        let head = {
+            let mut inner = self.inner.exclusive_access();
+            let head = if request_type == REQUEST_OUT {
+                inner.queue.add(&[header, buffer], &[status])
+            } else {
+                inner.queue.add(&[header], &[buffer, status])
+            }
+            .expect("virtio-blk queue is full");
+            inner.mmio.notify(0);
+            head
+        };
+        self.wait(head);
+
+        assert_eq!(
+            bytes[STATUS_OFFSET], STATUS_OK,
+            "virtio-blk request on sector {} failed",
+            block_id
+        );
+        if request_type == REQUEST_IN {
+            data.copy_from_slice(&bytes[DATA_OFFSET..DATA_OFFSET + BLOCK_SIZE]);
+        }
+    }
+
+    fn wait(&self, head: u16) {
+        loop {
+            {
+                let mut inner = self.inner.exclusive_access();
+                inner.collect_used();
+                if inner.done[head as usize] {
+                    inner.done[head as usize] = false;
+                    return;
+                }
+            }
+            match self.irq {
+                // the handler collects the request, so the borrow above must be released
+                Some(_) => plic::wait_for_external_interrupt(),
+                None => core::hint::spin_loop(),
+            }
+        }
+    }
+}
+
+impl BlockDevice for VirtIOBlk {
+    fn read_block(&self, block_id: usize, buf: &mut [u8]) {
+        self.request(REQUEST_IN, block_id, buf);
+    }
+
+    fn write_block(&self, block_id: usize, buf: &[u8]) {
+        let mut data = [0u8; BLOCK_SIZE];
+        data.copy_from_slice(buf);
+        self.request(REQUEST_OUT, block_id, &mut data);
+    }
+
+    fn block_count(&self) -> usize {
+        self.capacity
+    }
+
+    fn handle_irq(&self) {
+        let mut inner = self.inner.exclusive_access();
+        if inner.mmio.ack_interrupt() {
+            inner.collect_used();
+        }
+    }
+}
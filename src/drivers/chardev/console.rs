@@ -0,0 +1,42 @@
+//! The console of `hal::console` as a character device
+
+use super::CharDevice;
+use crate::hal::console::{getchar, write_bytes};
+use crate::task::sche::suspend_current;
+use crate::task::signal::signal_pending;
+
+pub struct ConsoleDevice;
+
+/// Fill `slots` with console input: block until at least one character arrives, then
+/// take what is available. Return the number of slots filled, 0 only if a signal came
+/// first. Both stdin and /dev/console read through it.
+pub fn read_console<'a>(slots: impl Iterator<Item = &'a mut u8>) -> usize {
+    let mut len = 0;
+    for slot in slots {
+        loop {
+            match getchar() {
+                Some(c) => {
+                    *slot = c;
+                    len += 1;
+                    break;
+                }
+                // yield the cpu while waiting for the first character, unless a signal
+                // has to be handled
+                None if len == 0 && !signal_pending() => suspend_current(),
+                None => return len,
+            }
+        }
+    }
+    len
+}
+
+impl CharDevice for ConsoleDevice {
+    fn read(&self, buf: &mut [u8]) -> usize {
+        read_console(buf.iter_mut())
+    }
+
+    fn write(&self, buf: &[u8]) -> usize {
+        write_bytes(buf);
+        buf.len()
+    }
+}
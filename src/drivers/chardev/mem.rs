@@ -0,0 +1,30 @@
+//! /dev/null and /dev/zero
+
+use super::CharDevice;
+
+/// Reads nothing, swallows every write
+pub struct NullDevice;
+
+impl CharDevice for NullDevice {
+    fn read(&self, _buf: &mut [u8]) -> usize {
+        0
+    }
+
+    fn write(&self, buf: &[u8]) -> usize {
+        buf.len()
+    }
+}
+
+/// Reads zeros, swallows every write
+pub struct ZeroDevice;
+
+impl CharDevice for ZeroDevice {
+    fn read(&self, buf: &mut [u8]) -> usize {
+        buf.fill(0);
+        buf.len()
+    }
+
+    fn write(&self, buf: &[u8]) -> usize {
+        buf.len()
+    }
+}
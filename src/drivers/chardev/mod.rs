@@ -0,0 +1,49 @@
+//! Character devices, byte streams registered by name and shown in devfs
+pub mod console;
+pub mod mem;
+pub mod random;
+
+use crate::sync::upsafecell::UPSafeCell;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use lazy_static::*;
+
+pub use console::ConsoleDevice;
+pub use mem::{NullDevice, ZeroDevice};
+pub use random::RandomDevice;
+
+pub trait CharDevice: Send + Sync {
+    /// read into `buf`, return the number of bytes read, 0 at the end of the stream
+    fn read(&self, buf: &mut [u8]) -> usize;
+    /// write `buf`, return the number of bytes written
+    fn write(&self, buf: &[u8]) -> usize;
+}
+
+lazy_static! {
+    /// character devices, in registration order
+    static ref CHAR_DEVICES: UPSafeCell<Vec<(&'static str, Arc<dyn CharDevice>)>> =
+        unsafe { UPSafeCell::new(Vec::new()) };
+}
+
+/// Register `device` as `name`, return false if the name is taken.
+pub fn register_char_device(name: &'static str, device: Arc<dyn CharDevice>) -> bool {
+    let mut devices = CHAR_DEVICES.exclusive_access();
+    if devices.iter().any(|(taken, _)| *taken == name) {
+        return false;
+    }
+    devices.push((name, device));
+    true
+}
+
+/// (name, device) of every character device, in registration order
+pub fn char_devices() -> Vec<(&'static str, Arc<dyn CharDevice>)> {
+    CHAR_DEVICES.exclusive_access().clone()
+}
+
+/// Register the devices every board has.
+pub fn init() {
+    register_char_device("null", Arc::new(NullDevice));
+    register_char_device("zero", Arc::new(ZeroDevice));
+    register_char_device("console", Arc::new(ConsoleDevice));
+    register_char_device("urandom", Arc::new(RandomDevice::new()));
+}
@@ -0,0 +1,51 @@
+//! /dev/urandom, a xorshift64* generator seeded from the clocks
+//!
+//! Fine for tests and shuffling, not for cryptography.
+
+use super::CharDevice;
+use crate::hal::rtc::read_time_ns;
+use crate::hal::syscall::get_time_ms;
+use crate::sync::upsafecell::UPSafeCell;
+
+pub struct RandomDevice {
+    state: UPSafeCell<u64>,
+}
+
+impl RandomDevice {
+    pub fn new() -> Self {
+        // the state must not be zero
+        let seed = (read_time_ns() ^ (get_time_ms() as u64).rotate_left(32)) | 1;
+        Self {
+            state: unsafe { UPSafeCell::new(seed) },
+        }
+    }
+
+    fn next(&self) -> u64 {
+        let mut state = self.state.exclusive_access();
+        *state ^= *state >> 12;
+        *state ^= *state << 25;
+        *state ^= *state >> 27;
+        state.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+}
+
+impl Default for RandomDevice {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CharDevice for RandomDevice {
+    fn read(&self, buf: &mut [u8]) -> usize {
+        for chunk in buf.chunks_mut(8) {
+            let bytes = self.next().to_le_bytes();
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
+        }
+        buf.len()
+    }
+
+    /// Writes are accepted and ignored, as nothing is mixed into the state.
+    fn write(&self, buf: &[u8]) -> usize {
+        buf.len()
+    }
+}
@@ -0,0 +1,49 @@
+//! Device drivers that are not tied to an architecture
+pub mod block;
+pub mod chardev;
+pub mod virtio;
+
+use self::block::{register_block_device, BlockDevice, VirtIOBlk};
+use self::virtio::{VirtIOMmio, DEVICE_ID_BLOCK};
+use crate::hal::*;
+use crate::println;
+use alloc::sync::Arc;
+
+/// Register the character devices and probe the virtio transports of the device tree,
+/// needs the frame allocator.
+pub fn init() {
+    chardev::init();
+    for device in board::virtio_devices() {
+        let mmio = match VirtIOMmio::probe(device.base) {
+            Some(mmio) => mmio,
+            None => continue,
+        };
+        match mmio.device_id() {
+            DEVICE_ID_BLOCK => {
+                let irq = if device.irq != 0 {
+                    Some(device.irq)
+                } else {
+                    None
+                };
+                match VirtIOBlk::new(mmio, irq) {
+                    Some(blk) => {
+                        println!(
+                            "[kernel] virtio-blk at {:#x}: {} sectors",
+                            device.base,
+                            blk.block_count()
+                        );
+                        register_block_device(Arc::new(blk));
+                        if let Some(irq) = irq {
+                            plic::register_irq(irq, block::handle_irq);
+                        }
+                    }
+                    None => println!("[kernel] virtio-blk at {:#x}: setup failed", device.base),
+                }
+            }
+            id => println!(
+                "[kernel] virtio device {} at {:#x} ignored",
+                id, device.base
+            ),
+        }
+    }
+}
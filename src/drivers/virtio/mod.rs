@@ -0,0 +1,178 @@
+//! Virtio over mmio, both the legacy (version 1) and the modern (version 2) register layout
+//!
+//! See the virtio specification, section 4.2.
+pub mod queue;
+
+use self::queue::{VirtQueue, QUEUE_SIZE, USED_ALIGN};
+use crate::sysconfig::PAGE_SIZE;
+use core::ptr::{read_volatile, write_volatile};
+
+const MAGIC_VALUE: usize = 0x000;
+const VERSION: usize = 0x004;
+const DEVICE_ID: usize = 0x008;
+const DEVICE_FEATURES: usize = 0x010;
+const DEVICE_FEATURES_SEL: usize = 0x014;
+const DRIVER_FEATURES: usize = 0x020;
+const DRIVER_FEATURES_SEL: usize = 0x024;
+/// legacy only
+const GUEST_PAGE_SIZE: usize = 0x028;
+const QUEUE_SEL: usize = 0x030;
+const QUEUE_NUM_MAX: usize = 0x034;
+const QUEUE_NUM: usize = 0x038;
+/// legacy only
+const QUEUE_ALIGN: usize = 0x03c;
+/// legacy only
+const QUEUE_PFN: usize = 0x040;
+const QUEUE_READY: usize = 0x044;
+const QUEUE_NOTIFY: usize = 0x050;
+const INTERRUPT_STATUS: usize = 0x060;
+const INTERRUPT_ACK: usize = 0x064;
+const STATUS: usize = 0x070;
+const QUEUE_DESC_LOW: usize = 0x080;
+const QUEUE_DESC_HIGH: usize = 0x084;
+const QUEUE_DRIVER_LOW: usize = 0x090;
+const QUEUE_DRIVER_HIGH: usize = 0x094;
+const QUEUE_DEVICE_LOW: usize = 0x0a0;
+const QUEUE_DEVICE_HIGH: usize = 0x0a4;
+const CONFIG: usize = 0x100;
+
+/// "virt" in little endian
+const MAGIC: u32 = 0x7472_6976;
+
+const STATUS_ACKNOWLEDGE: u32 = 1;
+const STATUS_DRIVER: u32 = 2;
+const STATUS_DRIVER_OK: u32 = 4;
+const STATUS_FEATURES_OK: u32 = 8;
+const STATUS_FAILED: u32 = 128;
+
+/// the device follows the virtio 1.0 specification, must be accepted on modern devices
+pub const F_VERSION_1: u64 = 1 << 32;
+
+pub const DEVICE_ID_NET: u32 = 1;
+pub const DEVICE_ID_BLOCK: u32 = 2;
+pub const DEVICE_ID_CONSOLE: u32 = 3;
+
+pub struct VirtIOMmio {
+    base: usize,
+    version: u32,
+}
+
+impl VirtIOMmio {
+    /// Return None if there is no device behind `base`,
+    /// qemu describes every transport slot even when nothing is plugged in.
+    pub fn probe(base: usize) -> Option<Self> {
+        let mmio = Self { base, version: 0 };
+        if mmio.read_reg(MAGIC_VALUE) != MAGIC {
+            return None;
+        }
+        let version = mmio.read_reg(VERSION);
+        if !(version == 1 || version == 2) || mmio.read_reg(DEVICE_ID) == 0 {
+            return None;
+        }
+        Some(Self { base, version })
+    }
+
+    fn read_reg(&self, reg: usize) -> u32 {
+        unsafe { read_volatile((self.base + reg) as *const u32) }
+    }
+
+    fn write_reg(&self, reg: usize, value: u32) {
+        unsafe { write_volatile((self.base + reg) as *mut u32, value) }
+    }
+
+    pub fn device_id(&self) -> u32 {
+        self.read_reg(DEVICE_ID)
+    }
+
+    pub fn is_legacy(&self) -> bool {
+        self.version == 1
+    }
+
+    /// Reset the device and negotiate features, return the accepted ones.
+    /// Return None and mark the device failed if it rejects them.
+    pub fn init(&self, supported_features: u64) -> Option<u64> {
+        self.write_reg(STATUS, 0);
+        self.write_reg(STATUS, STATUS_ACKNOWLEDGE);
+        self.write_reg(STATUS, STATUS_ACKNOWLEDGE | STATUS_DRIVER);
+
+        self.write_reg(DEVICE_FEATURES_SEL, 0);
+        let mut device_features = self.read_reg(DEVICE_FEATURES) as u64;
+        self.write_reg(DEVICE_FEATURES_SEL, 1);
+        device_features |= (self.read_reg(DEVICE_FEATURES) as u64) << 32;
+        let mut features = device_features & supported_features;
+        if !self.is_legacy() {
+            features |= device_features & F_VERSION_1;
+        }
+        self.write_reg(DRIVER_FEATURES_SEL, 0);
+        self.write_reg(DRIVER_FEATURES, features as u32);
+        self.write_reg(DRIVER_FEATURES_SEL, 1);
+        self.write_reg(DRIVER_FEATURES, (features >> 32) as u32);
+
+        if self.is_legacy() {
+            // legacy devices have no FEATURES_OK handshake
+            self.write_reg(GUEST_PAGE_SIZE, PAGE_SIZE as u32);
+            return Some(features);
+        }
+        let status = STATUS_ACKNOWLEDGE | STATUS_DRIVER | STATUS_FEATURES_OK;
+        self.write_reg(STATUS, status);
+        if self.read_reg(STATUS) & STATUS_FEATURES_OK == 0 {
+            self.write_reg(STATUS, status | STATUS_FAILED);
+            return None;
+        }
+        Some(features)
+    }
+
+    /// hand `queue` to the device as its queue number `index`
+    pub fn setup_queue(&self, index: u32, queue: &VirtQueue) -> bool {
+        self.write_reg(QUEUE_SEL, index);
+        let max = self.read_reg(QUEUE_NUM_MAX) as usize;
+        if max < QUEUE_SIZE {
+            return false;
+        }
+        self.write_reg(QUEUE_NUM, QUEUE_SIZE as u32);
+        if self.is_legacy() {
+            self.write_reg(QUEUE_ALIGN, USED_ALIGN as u32);
+            self.write_reg(QUEUE_PFN, (queue.desc_addr() / PAGE_SIZE) as u32);
+        } else {
+            let (desc, avail, used) = (queue.desc_addr(), queue.avail_addr(), queue.used_addr());
+            self.write_reg(QUEUE_DESC_LOW, desc as u32);
+            self.write_reg(QUEUE_DESC_HIGH, (desc >> 32) as u32);
+            self.write_reg(QUEUE_DRIVER_LOW, avail as u32);
+            self.write_reg(QUEUE_DRIVER_HIGH, (avail >> 32) as u32);
+            self.write_reg(QUEUE_DEVICE_LOW, used as u32);
+            self.write_reg(QUEUE_DEVICE_HIGH, (used >> 32) as u32);
+            self.write_reg(QUEUE_READY, 1);
+        }
+        true
+    }
+
+    /// the driver is set up, the device may start working
+    pub fn driver_ok(&self) {
+        let status = self.read_reg(STATUS);
+        self.write_reg(STATUS, status | STATUS_DRIVER_OK);
+    }
+
+    /// tell the device there are new buffers in queue `index`
+    pub fn notify(&self, index: u32) {
+        self.write_reg(QUEUE_NOTIFY, index);
+    }
+
+    /// acknowledge the pending interrupt, return false if the device did not interrupt
+    pub fn ack_interrupt(&self) -> bool {
+        let status = self.read_reg(INTERRUPT_STATUS);
+        if status == 0 {
+            return false;
+        }
+        self.write_reg(INTERRUPT_ACK, status);
+        true
+    }
+
+    /// read the device specific configuration space
+    pub fn config_u32(&self, offset: usize) -> u32 {
+        self.read_reg(CONFIG + offset)
+    }
+
+    pub fn config_u64(&self, offset: usize) -> u64 {
+        self.config_u32(offset) as u64 | (self.config_u32(offset + 4) as u64) << 32
+    }
+}
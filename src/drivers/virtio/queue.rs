@@ -0,0 +1,185 @@
+//! Split virtqueue
+//!
+//! The descriptor table, the available ring and the used ring of a queue all live in one
+//! frame, so the queue works with both the legacy and the modern mmio transport.
+use crate::hal::*;
+use crate::mm::page_table::frame::{frame_alloc, FrameTracker};
+use crate::sysconfig::PAGE_SIZE;
+use core::mem::size_of;
+use core::ptr::{addr_of, addr_of_mut, read_volatile, write_volatile};
+use core::sync::atomic::{fence, Ordering};
+
+/// number of descriptors of every queue
+pub const QUEUE_SIZE: usize = 16;
+
+/// the buffer continues in the `next` descriptor
+const DESC_F_NEXT: u16 = 1;
+/// the buffer is written by the device
+const DESC_F_WRITE: u16 = 2;
+/// ask the device not to interrupt when it consumes a buffer
+const AVAIL_F_NO_INTERRUPT: u16 = 1;
+
+#[repr(C)]
+struct Descriptor {
+    addr: u64,
+    len: u32,
+    flags: u16,
+    next: u16,
+}
+
+#[repr(C)]
+struct AvailRing {
+    flags: u16,
+    idx: u16,
+    ring: [u16; QUEUE_SIZE],
+    used_event: u16,
+}
+
+#[repr(C)]
+struct UsedElem {
+    id: u32,
+    len: u32,
+}
+
+#[repr(C)]
+struct UsedRing {
+    flags: u16,
+    idx: u16,
+    ring: [UsedElem; QUEUE_SIZE],
+    avail_event: u16,
+}
+
+const AVAIL_OFFSET: usize = size_of::<Descriptor>() * QUEUE_SIZE;
+/// alignment of the used ring, told to legacy devices through `QueueAlign`
+pub const USED_ALIGN: usize = 4;
+const USED_OFFSET: usize =
+    (AVAIL_OFFSET + size_of::<AvailRing>() + USED_ALIGN - 1) & !(USED_ALIGN - 1);
+
+pub struct VirtQueue {
+    frame: FrameTracker,
+    /// first descriptor of the free list, chained by `next`
+    free_head: u16,
+    num_free: u16,
+    /// next index of the available ring to fill
+    avail_idx: u16,
+    /// next index of the used ring to consume
+    last_used_idx: u16,
+}
+
+impl VirtQueue {
+    pub fn new() -> Self {
+        assert!(USED_OFFSET + size_of::<UsedRing>() <= PAGE_SIZE);
+        let queue = Self {
+            frame: frame_alloc().unwrap(),
+            free_head: 0,
+            num_free: QUEUE_SIZE as u16,
+            avail_idx: 0,
+            last_used_idx: 0,
+        };
+        for i in 0..QUEUE_SIZE - 1 {
+            queue.desc(i as u16).next = i as u16 + 1;
+        }
+        queue
+    }
+
+    /// physical address of the descriptor table, also the start of the queue
+    pub fn desc_addr(&self) -> usize {
+        self.frame.ppn.get_bytes_array_mut().as_mut_ptr() as usize
+    }
+
+    pub fn avail_addr(&self) -> usize {
+        self.desc_addr() + AVAIL_OFFSET
+    }
+
+    pub fn used_addr(&self) -> usize {
+        self.desc_addr() + USED_OFFSET
+    }
+
+    fn desc(&self, index: u16) -> &'static mut Descriptor {
+        let ptr = self.desc_addr() as *mut Descriptor;
+        unsafe { &mut *ptr.add(index as usize) }
+    }
+
+    fn avail(&self) -> *mut AvailRing {
+        self.avail_addr() as *mut AvailRing
+    }
+
+    fn used(&self) -> *mut UsedRing {
+        self.used_addr() as *mut UsedRing
+    }
+
+    /// whether the device should interrupt after it has used a buffer
+    pub fn set_interrupt(&mut self, enabled: bool) {
+        let flags = if enabled { 0 } else { AVAIL_F_NO_INTERRUPT };
+        unsafe { write_volatile(addr_of_mut!((*self.avail()).flags), flags) }
+    }
+
+    /// Chain `(addr, len)` buffers into one request and make it available to the device.
+    /// `inputs` are read by the device, `outputs` are written by it.
+    /// Return the head descriptor, which identifies the request when it is used.
+    pub fn add(&mut self, inputs: &[(usize, usize)], outputs: &[(usize, usize)]) -> Option<u16> {
+        let count = inputs.len() + outputs.len();
+        if count == 0 || count > self.num_free as usize {
+            return None;
+        }
+        let head = self.free_head;
+        let mut last = head;
+        let buffers = inputs
+            .iter()
+            .map(|&buffer| (buffer, 0))
+            .chain(outputs.iter().map(|&buffer| (buffer, DESC_F_WRITE)));
+        for ((addr, len), flags) in buffers {
+            let desc = self.desc(self.free_head);
+            desc.addr = addr as u64;
+            desc.len = len as u32;
+            desc.flags = flags | DESC_F_NEXT;
+            last = self.free_head;
+            self.free_head = desc.next;
+        }
+        self.desc(last).flags &= !DESC_F_NEXT;
+        self.num_free -= count as u16;
+
+        let slot = self.avail_idx as usize % QUEUE_SIZE;
+        unsafe {
+            write_volatile(addr_of_mut!((*self.avail()).ring[slot]), head);
+        }
+        // the descriptors must be visible before the index that publishes them
+        fence(Ordering::SeqCst);
+        self.avail_idx = self.avail_idx.wrapping_add(1);
+        unsafe {
+            write_volatile(addr_of_mut!((*self.avail()).idx), self.avail_idx);
+        }
+        fence(Ordering::SeqCst);
+        Some(head)
+    }
+
+    /// Take the next request used by the device and free its descriptors.
+    /// Return its head descriptor and the number of bytes the device wrote.
+    pub fn pop_used(&mut self) -> Option<(u16, u32)> {
+        fence(Ordering::SeqCst);
+        let used_idx = unsafe { read_volatile(addr_of!((*self.used()).idx)) };
+        if used_idx == self.last_used_idx {
+            return None;
+        }
+        let slot = self.last_used_idx as usize % QUEUE_SIZE;
+        let (id, len) = unsafe {
+            let elem = addr_of!((*self.used()).ring[slot]);
+            (
+                read_volatile(addr_of!((*elem).id)),
+                read_volatile(addr_of!((*elem).len)),
+            )
+        };
+        self.last_used_idx = self.last_used_idx.wrapping_add(1);
+
+        let head = id as u16;
+        let mut last = head;
+        self.num_free += 1;
+        while self.desc(last).flags & DESC_F_NEXT != 0 {
+            last = self.desc(last).next;
+            self.num_free += 1;
+        }
+        self.desc(last).next = self.free_head;
+        self.free_head = head;
+        Some((head, len))
+    }
+}
@@ -0,0 +1,155 @@
+//! devfs, a node for every registered character and block device
+//!
+//! Block devices go through the block cache, so they stay consistent with the file systems
+//! mounted from them.
+use crate::drivers::block::{
+    block_device_name, cached_block_device, read_bytes, write_bytes, BlockDevice, BLOCK_SIZE,
+};
+use crate::drivers::chardev::{char_devices, CharDevice};
+use crate::fs::{Inode, Stat, StatMode};
+use alloc::string::{String, ToString};
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::any::Any;
+use core::cmp::min;
+
+const ROOT_INO: u64 = 1;
+const CHAR_INO_BASE: u64 = 0x100;
+const BLOCK_INO_BASE: u64 = 0x200;
+
+/// A character device, offsets are ignored
+struct CharDevInode {
+    ino: u64,
+    device: Arc<dyn CharDevice>,
+}
+
+impl Inode for CharDevInode {
+    fn read_at(&self, _offset: usize, buf: &mut [u8]) -> usize {
+        self.device.read(buf)
+    }
+
+    fn write_at(&self, _offset: usize, buf: &[u8]) -> usize {
+        self.device.write(buf)
+    }
+
+    fn stat(&self) -> Stat {
+        Stat::new(self.ino, StatMode::CHR, 0)
+    }
+
+    /// Nothing to drop, so opening with O_TRUNC works like on Linux.
+    fn truncate(&self) -> bool {
+        true
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+/// A block device, as one file of its whole size
+struct BlockDevInode {
+    ino: u64,
+    device: Arc<dyn BlockDevice>,
+}
+
+impl BlockDevInode {
+    fn size(&self) -> usize {
+        self.device.block_count() * BLOCK_SIZE
+    }
+}
+
+impl Inode for BlockDevInode {
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> usize {
+        if offset >= self.size() {
+            return 0;
+        }
+        let len = min(buf.len(), self.size() - offset);
+        read_bytes(self.device.as_ref(), offset, &mut buf[..len]);
+        len
+    }
+
+    fn write_at(&self, offset: usize, buf: &[u8]) -> usize {
+        if offset >= self.size() {
+            return 0;
+        }
+        let len = min(buf.len(), self.size() - offset);
+        write_bytes(self.device.as_ref(), offset, &buf[..len]);
+        len
+    }
+
+    fn stat(&self) -> Stat {
+        Stat::new(self.ino, StatMode::BLK, self.size() as u64)
+    }
+
+    fn truncate(&self) -> bool {
+        true
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+/// The root of devfs, listing the devices registered now
+pub struct DevRoot;
+
+impl DevRoot {
+    fn block_devices() -> Vec<(String, Arc<dyn BlockDevice>)> {
+        let mut devices = Vec::new();
+        while let Some(device) = cached_block_device(devices.len()) {
+            devices.push((
+                block_device_name(devices.len()),
+                device as Arc<dyn BlockDevice>,
+            ));
+        }
+        devices
+    }
+}
+
+impl Inode for DevRoot {
+    fn read_at(&self, _offset: usize, _buf: &mut [u8]) -> usize {
+        0
+    }
+
+    fn write_at(&self, _offset: usize, _buf: &[u8]) -> usize {
+        0
+    }
+
+    fn stat(&self) -> Stat {
+        Stat::new(ROOT_INO, StatMode::DIR, 0)
+    }
+
+    fn lookup(&self, name: &str) -> Option<Arc<dyn Inode>> {
+        let char_device = char_devices()
+            .into_iter()
+            .enumerate()
+            .find(|(_, (device_name, _))| *device_name == name);
+        if let Some((index, (_, device))) = char_device {
+            return Some(Arc::new(CharDevInode {
+                ino: CHAR_INO_BASE + index as u64,
+                device,
+            }));
+        }
+        let (index, (_, device)) = Self::block_devices()
+            .into_iter()
+            .enumerate()
+            .find(|(_, (device_name, _))| device_name == name)?;
+        Some(Arc::new(BlockDevInode {
+            ino: BLOCK_INO_BASE + index as u64,
+            device,
+        }))
+    }
+
+    fn list(&self) -> Vec<String> {
+        let mut names: Vec<String> = char_devices()
+            .iter()
+            .map(|(name, _)| name.to_string())
+            .collect();
+        names.extend(Self::block_devices().into_iter().map(|(name, _)| name));
+        names
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
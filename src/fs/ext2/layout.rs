@@ -0,0 +1,208 @@
+//! On-disk structures of ext2, all little endian
+
+fn u16_at(data: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([data[offset], data[offset + 1]])
+}
+
+fn u32_at(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([
+        data[offset],
+        data[offset + 1],
+        data[offset + 2],
+        data[offset + 3],
+    ])
+}
+
+/// byte offset of the superblock on the volume
+pub const SUPERBLOCK_OFFSET: usize = 1024;
+pub const SUPERBLOCK_SIZE: usize = 1024;
+const EXT2_MAGIC: u16 = 0xef53;
+/// revision 0 has fixed inode size and no feature flags
+const GOOD_OLD_REV: u32 = 0;
+const GOOD_OLD_INODE_SIZE: usize = 128;
+
+/// directory entries record their file type
+const INCOMPAT_FILETYPE: u32 = 0x0002;
+/// groups are packed together, only placement changes
+const INCOMPAT_FLEX_BG: u32 = 0x0200;
+const SUPPORTED_INCOMPAT: u32 = INCOMPAT_FILETYPE | INCOMPAT_FLEX_BG;
+/// i_size_high holds the high 32 bits of the size of regular files
+const RO_COMPAT_LARGE_FILE: u32 = 0x0002;
+
+pub const ROOT_INO: u32 = 2;
+/// i_block holds 12 direct blocks, then a single, a double and a triple indirect one
+pub const DIRECT_BLOCKS: usize = 12;
+pub const INODE_BLOCK_POINTERS: usize = 15;
+/// fast symlinks keep their target in i_block
+pub const FAST_SYMLINK_MAX: usize = INODE_BLOCK_POINTERS * 4;
+
+pub const GROUP_DESC_SIZE: usize = 32;
+
+#[derive(Debug, Clone, Copy)]
+pub struct SuperBlock {
+    pub inodes_count: u32,
+    pub blocks_count: u32,
+    pub first_data_block: u32,
+    pub block_size: usize,
+    pub blocks_per_group: u32,
+    pub inodes_per_group: u32,
+    pub inode_size: usize,
+    pub has_file_type: bool,
+    pub large_file: bool,
+}
+
+impl SuperBlock {
+    /// Parse the superblock, return None if it is not a supported ext2 volume.
+    pub fn parse(data: &[u8]) -> Option<Self> {
+        if u16_at(data, 56) != EXT2_MAGIC {
+            return None;
+        }
+        // larger blocks encode the length of directory entries differently
+        let log_block_size = u32_at(data, 24);
+        if log_block_size > 2 {
+            return None;
+        }
+        let rev_level = u32_at(data, 76);
+        let (inode_size, incompat, ro_compat) = if rev_level == GOOD_OLD_REV {
+            (GOOD_OLD_INODE_SIZE, 0, 0)
+        } else {
+            (
+                u16_at(data, 88) as usize,
+                u32_at(data, 96),
+                u32_at(data, 100),
+            )
+        };
+        // journal recovery, extents, 64 bit and the like belong to ext3 and ext4
+        if incompat & !SUPPORTED_INCOMPAT != 0 {
+            return None;
+        }
+        let super_block = Self {
+            inodes_count: u32_at(data, 0),
+            blocks_count: u32_at(data, 4),
+            first_data_block: u32_at(data, 20),
+            block_size: 1024 << log_block_size,
+            blocks_per_group: u32_at(data, 32),
+            inodes_per_group: u32_at(data, 40),
+            inode_size,
+            has_file_type: incompat & INCOMPAT_FILETYPE != 0,
+            large_file: ro_compat & RO_COMPAT_LARGE_FILE != 0,
+        };
+        if super_block.blocks_per_group == 0
+            || super_block.inodes_per_group == 0
+            || super_block.inode_size < GOOD_OLD_INODE_SIZE
+            || super_block.inode_size > super_block.block_size
+            || super_block.group_count().is_none()
+        {
+            return None;
+        }
+        Some(super_block)
+    }
+
+    /// None if the first data block is past the end of the volume
+    pub fn group_count(&self) -> Option<usize> {
+        let data_blocks = self.blocks_count.checked_sub(self.first_data_block)? as usize;
+        Some((data_blocks + self.blocks_per_group as usize - 1) / self.blocks_per_group as usize)
+    }
+
+    /// block holding the first group descriptor
+    pub fn group_desc_block(&self) -> usize {
+        self.first_data_block as usize + 1
+    }
+}
+
+/// the part of a group descriptor needed to find inodes
+pub fn inode_table_of(group_desc: &[u8]) -> u32 {
+    u32_at(group_desc, 8)
+}
+
+/// file type bits of i_mode
+pub const S_IFMT: u16 = 0xf000;
+pub const S_IFCHR: u16 = 0x2000;
+pub const S_IFDIR: u16 = 0x4000;
+pub const S_IFBLK: u16 = 0x6000;
+pub const S_IFREG: u16 = 0x8000;
+pub const S_IFLNK: u16 = 0xa000;
+
+#[derive(Debug, Clone, Copy)]
+pub struct DiskInode {
+    pub mode: u16,
+    pub size: u64,
+    pub links_count: u16,
+    /// 512 bytes sectors used, extended attribute block included
+    pub sectors: u32,
+    pub file_acl: u32,
+    pub block: [u32; INODE_BLOCK_POINTERS],
+}
+
+impl DiskInode {
+    pub fn parse(data: &[u8], large_file: bool) -> Self {
+        let mode = u16_at(data, 0);
+        let mut size = u32_at(data, 4) as u64;
+        if large_file && mode & S_IFMT == S_IFREG {
+            size |= (u32_at(data, 108) as u64) << 32;
+        }
+        let mut block = [0u32; INODE_BLOCK_POINTERS];
+        for (i, pointer) in block.iter_mut().enumerate() {
+            *pointer = u32_at(data, 40 + i * 4);
+        }
+        Self {
+            mode,
+            size,
+            links_count: u16_at(data, 26),
+            sectors: u32_at(data, 28),
+            file_acl: u32_at(data, 104),
+            block,
+        }
+    }
+
+    pub fn file_type(&self) -> u16 {
+        self.mode & S_IFMT
+    }
+
+    pub fn is_dir(&self) -> bool {
+        self.file_type() == S_IFDIR
+    }
+
+    /// a fast symlink uses no block but the extended attribute one
+    pub fn is_fast_symlink(&self, block_size: usize) -> bool {
+        let attr_sectors = if self.file_acl != 0 {
+            (block_size / 512) as u32
+        } else {
+            0
+        };
+        self.file_type() == S_IFLNK && self.sectors == attr_sectors
+    }
+
+    /// i_block as raw bytes, where fast symlinks keep their target
+    pub fn block_bytes(&self) -> [u8; FAST_SYMLINK_MAX] {
+        let mut bytes = [0u8; FAST_SYMLINK_MAX];
+        for (i, pointer) in self.block.iter().enumerate() {
+            bytes[i * 4..i * 4 + 4].copy_from_slice(&pointer.to_le_bytes());
+        }
+        bytes
+    }
+}
+
+/// header of a directory entry, the name follows it
+pub const DIR_ENTRY_HEADER: usize = 8;
+
+pub struct DirEntryHeader {
+    pub inode: u32,
+    pub rec_len: usize,
+    pub name_len: usize,
+}
+
+impl DirEntryHeader {
+    pub fn parse(data: &[u8], has_file_type: bool) -> Self {
+        let name_len = if has_file_type {
+            data[6] as usize
+        } else {
+            u16_at(data, 6) as usize
+        };
+        Self {
+            inode: u32_at(data, 0),
+            rec_len: u16_at(data, 4) as usize,
+            name_len,
+        }
+    }
+}
@@ -0,0 +1,241 @@
+//! Read-only ext2, to boot from root file systems made by `mke2fs -d` on the host
+mod layout;
+
+use self::layout::*;
+use crate::drivers::block::{read_bytes, BlockDevice};
+use crate::fs::{Inode, Stat, StatMode};
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::any::Any;
+use core::cmp::min;
+
+pub struct Ext2FileSystem {
+    device: Arc<dyn BlockDevice>,
+    super_block: SuperBlock,
+    /// inode table of every group
+    inode_tables: Vec<u32>,
+}
+
+impl Ext2FileSystem {
+    fn block_size(&self) -> usize {
+        self.super_block.block_size
+    }
+
+    fn read_block_bytes(&self, block_id: u32, offset: usize, buf: &mut [u8]) {
+        read_bytes(
+            self.device.as_ref(),
+            block_id as usize * self.block_size() + offset,
+            buf,
+        );
+    }
+
+    fn read_inode(&self, ino: u32) -> Option<DiskInode> {
+        if ino == 0 || ino > self.super_block.inodes_count {
+            return None;
+        }
+        let index = (ino - 1) as usize;
+        let inodes_per_group = self.super_block.inodes_per_group as usize;
+        let table = *self.inode_tables.get(index / inodes_per_group)?;
+        let mut data = vec![0u8; self.super_block.inode_size];
+        self.read_block_bytes(
+            table,
+            (index % inodes_per_group) * self.super_block.inode_size,
+            &mut data,
+        );
+        Some(DiskInode::parse(&data, self.super_block.large_file))
+    }
+
+    /// follow the indirect blocks from `block` along `path`, 0 is a hole
+    fn indirect_block_id(&self, mut block: u32, path: &[usize]) -> u32 {
+        for &index in path {
+            if block == 0 {
+                return 0;
+            }
+            let mut raw = [0u8; 4];
+            self.read_block_bytes(block, index * 4, &mut raw);
+            block = u32::from_le_bytes(raw);
+        }
+        block
+    }
+
+    /// volume block of the `inner_id`th block of a file, 0 is a hole
+    fn block_id(&self, inode: &DiskInode, inner_id: usize) -> u32 {
+        let per_block = self.block_size() / 4;
+        if inner_id < DIRECT_BLOCKS {
+            return inode.block[inner_id];
+        }
+        let mut index = inner_id - DIRECT_BLOCKS;
+        if index < per_block {
+            return self.indirect_block_id(inode.block[DIRECT_BLOCKS], &[index]);
+        }
+        index -= per_block;
+        if index < per_block * per_block {
+            return self.indirect_block_id(
+                inode.block[DIRECT_BLOCKS + 1],
+                &[index / per_block, index % per_block],
+            );
+        }
+        index -= per_block * per_block;
+        self.indirect_block_id(
+            inode.block[DIRECT_BLOCKS + 2],
+            &[
+                index / (per_block * per_block),
+                index / per_block % per_block,
+                index % per_block,
+            ],
+        )
+    }
+
+    fn read_inode_data(&self, inode: &DiskInode, offset: usize, buf: &mut [u8]) -> usize {
+        if offset as u64 >= inode.size {
+            return 0;
+        }
+        let len = min(buf.len() as u64, inode.size - offset as u64) as usize;
+        let block_size = self.block_size();
+        let mut done = 0;
+        while done < len {
+            let pos = offset + done;
+            let size = min(block_size - pos % block_size, len - done);
+            let target = &mut buf[done..done + size];
+            match self.block_id(inode, pos / block_size) {
+                0 => target.fill(0),
+                block_id => self.read_block_bytes(block_id, pos % block_size, target),
+            }
+            done += size;
+        }
+        len
+    }
+}
+
+pub struct Ext2Inode {
+    fs: Arc<Ext2FileSystem>,
+    ino: u32,
+    inode: DiskInode,
+}
+
+impl Ext2Inode {
+    fn new(fs: Arc<Ext2FileSystem>, ino: u32) -> Option<Arc<dyn Inode>> {
+        let inode = fs.read_inode(ino)?;
+        Some(Arc::new(Self { fs, ino, inode }))
+    }
+
+    /// (inode number, name) of every entry of this directory but "." and ".."
+    fn dir_entries(&self) -> Vec<(u32, String)> {
+        let mut entries = Vec::new();
+        if !self.inode.is_dir() {
+            return entries;
+        }
+        let block_size = self.fs.block_size();
+        let mut data = vec![0u8; block_size];
+        let mut offset = 0;
+        while (offset as u64) < self.inode.size {
+            let len = self.fs.read_inode_data(&self.inode, offset, &mut data);
+            let mut pos = 0;
+            while pos + DIR_ENTRY_HEADER <= len {
+                let header = DirEntryHeader::parse(&data[pos..], self.fs.super_block.has_file_type);
+                if header.rec_len < DIR_ENTRY_HEADER || pos + header.rec_len > len {
+                    break;
+                }
+                let name_start = pos + DIR_ENTRY_HEADER;
+                let name_end = min(name_start + header.name_len, pos + header.rec_len);
+                let name = String::from_utf8_lossy(&data[name_start..name_end]);
+                if header.inode != 0 && name != "." && name != ".." {
+                    entries.push((header.inode, name.into_owned()));
+                }
+                pos += header.rec_len;
+            }
+            offset += block_size;
+        }
+        entries
+    }
+}
+
+impl Inode for Ext2Inode {
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> usize {
+        if self.inode.is_dir() {
+            return 0;
+        }
+        self.fs.read_inode_data(&self.inode, offset, buf)
+    }
+
+    fn write_at(&self, _offset: usize, _buf: &[u8]) -> usize {
+        0
+    }
+
+    fn stat(&self) -> Stat {
+        let mode = match self.inode.file_type() {
+            S_IFDIR => StatMode::DIR,
+            S_IFLNK => StatMode::LNK,
+            S_IFCHR => StatMode::CHR,
+            S_IFBLK => StatMode::BLK,
+            S_IFREG => StatMode::FILE,
+            _ => StatMode::NULL,
+        };
+        let mut stat = Stat::new(self.ino as u64, mode, self.inode.size);
+        stat.nlink = self.inode.links_count as u32;
+        stat
+    }
+
+    fn lookup(&self, name: &str) -> Option<Arc<dyn Inode>> {
+        let (ino, _) = self
+            .dir_entries()
+            .into_iter()
+            .find(|(_, entry_name)| entry_name == name)?;
+        Ext2Inode::new(self.fs.clone(), ino)
+    }
+
+    fn list(&self) -> Vec<String> {
+        self.dir_entries()
+            .into_iter()
+            .map(|(_, name)| name)
+            .collect()
+    }
+
+    fn readlink(&self) -> Option<String> {
+        if self.inode.file_type() != S_IFLNK {
+            return None;
+        }
+        let len = self.inode.size as usize;
+        let target = if self.inode.is_fast_symlink(self.fs.block_size()) {
+            self.inode.block_bytes()[..min(len, FAST_SYMLINK_MAX)].to_vec()
+        } else {
+            let mut target = vec![0u8; len];
+            self.fs.read_inode_data(&self.inode, 0, &mut target);
+            target
+        };
+        String::from_utf8(target).ok()
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+/// Return the root directory of the ext2 volume on `device`, None if it holds none.
+pub fn mount(device: Arc<dyn BlockDevice>) -> Option<Arc<dyn Inode>> {
+    let mut data = vec![0u8; SUPERBLOCK_SIZE];
+    read_bytes(device.as_ref(), SUPERBLOCK_OFFSET, &mut data);
+    let super_block = SuperBlock::parse(&data)?;
+    let mut group_descs = vec![0u8; super_block.group_count()? * GROUP_DESC_SIZE];
+    read_bytes(
+        device.as_ref(),
+        super_block.group_desc_block() * super_block.block_size,
+        &mut group_descs,
+    );
+    let inode_tables = group_descs
+        .chunks(GROUP_DESC_SIZE)
+        .map(inode_table_of)
+        .collect();
+    let fs = Arc::new(Ext2FileSystem {
+        device,
+        super_block,
+        inode_tables,
+    });
+    let root = Ext2Inode::new(fs, ROOT_INO)?;
+    if root.stat().mode != StatMode::DIR {
+        return None;
+    }
+    Some(root)
+}
@@ -0,0 +1,96 @@
+//! Boot sector and FS information sector
+
+fn u16_at(sector: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([sector[offset], sector[offset + 1]])
+}
+
+fn u32_at(sector: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([
+        sector[offset],
+        sector[offset + 1],
+        sector[offset + 2],
+        sector[offset + 3],
+    ])
+}
+
+/// fewer clusters than this make the volume FAT12 or FAT16
+const MIN_FAT32_CLUSTERS: usize = 65525;
+
+const FSINFO_LEAD_SIGNATURE: u32 = 0x4161_5252;
+const FSINFO_STRUC_SIGNATURE: u32 = 0x6141_7272;
+/// offset of the free cluster count in the FS information sector
+pub const FSINFO_FREE_COUNT: usize = 488;
+
+/// The fields of the BIOS parameter block FAT32 needs
+#[derive(Debug, Clone, Copy)]
+pub struct BiosParameterBlock {
+    pub bytes_per_sector: usize,
+    pub sectors_per_cluster: usize,
+    pub reserved_sectors: usize,
+    pub num_fats: usize,
+    pub total_sectors: usize,
+    pub fat_size: usize,
+    pub root_cluster: u32,
+    /// sector of the FS information structure, 0 if there is none
+    pub fs_info_sector: usize,
+}
+
+impl BiosParameterBlock {
+    /// Parse the boot sector, return None if it does not describe a FAT32 volume.
+    pub fn parse(sector: &[u8]) -> Option<Self> {
+        if sector.len() < 512 || sector[510] != 0x55 || sector[511] != 0xaa {
+            return None;
+        }
+        let bpb = Self {
+            bytes_per_sector: u16_at(sector, 11) as usize,
+            sectors_per_cluster: sector[13] as usize,
+            reserved_sectors: u16_at(sector, 14) as usize,
+            num_fats: sector[16] as usize,
+            total_sectors: match u16_at(sector, 19) {
+                0 => u32_at(sector, 32) as usize,
+                total => total as usize,
+            },
+            fat_size: u32_at(sector, 36) as usize,
+            root_cluster: u32_at(sector, 44),
+            fs_info_sector: u16_at(sector, 48) as usize,
+        };
+        let root_entry_count = u16_at(sector, 17);
+        let fat_size_16 = u16_at(sector, 22);
+        if !matches!(bpb.bytes_per_sector, 512 | 1024 | 2048 | 4096)
+            || !bpb.sectors_per_cluster.is_power_of_two()
+            || bpb.reserved_sectors == 0
+            || bpb.num_fats == 0
+            || root_entry_count != 0
+            || fat_size_16 != 0
+            || bpb.fat_size == 0
+            || bpb.root_cluster < 2
+            || bpb.data_start_sector() >= bpb.total_sectors
+            || bpb.cluster_count() < MIN_FAT32_CLUSTERS
+        {
+            return None;
+        }
+        Some(bpb)
+    }
+
+    pub fn cluster_size(&self) -> usize {
+        self.bytes_per_sector * self.sectors_per_cluster
+    }
+
+    pub fn fat_start_sector(&self) -> usize {
+        self.reserved_sectors
+    }
+
+    pub fn data_start_sector(&self) -> usize {
+        self.reserved_sectors + self.num_fats * self.fat_size
+    }
+
+    /// number of data clusters, numbered from 2
+    pub fn cluster_count(&self) -> usize {
+        (self.total_sectors - self.data_start_sector()) / self.sectors_per_cluster
+    }
+}
+
+/// whether `sector` is a valid FS information sector
+pub fn is_fs_info(sector: &[u8]) -> bool {
+    u32_at(sector, 0) == FSINFO_LEAD_SIGNATURE && u32_at(sector, 484) == FSINFO_STRUC_SIGNATURE
+}
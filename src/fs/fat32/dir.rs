@@ -0,0 +1,220 @@
+//! Directory entries, short 8.3 names and VFAT long file names
+use alloc::string::String;
+use alloc::vec::Vec;
+
+pub const DIR_ENTRY_SIZE: usize = 32;
+
+pub const ATTR_READ_ONLY: u8 = 0x01;
+pub const ATTR_HIDDEN: u8 = 0x02;
+pub const ATTR_SYSTEM: u8 = 0x04;
+pub const ATTR_VOLUME_ID: u8 = 0x08;
+pub const ATTR_DIRECTORY: u8 = 0x10;
+pub const ATTR_ARCHIVE: u8 = 0x20;
+pub const ATTR_LONG_NAME: u8 = ATTR_READ_ONLY | ATTR_HIDDEN | ATTR_SYSTEM | ATTR_VOLUME_ID;
+
+/// first byte of an entry after the last one in use
+pub const ENTRY_END: u8 = 0x00;
+/// first byte of a deleted entry
+pub const ENTRY_FREE: u8 = 0xe5;
+
+/// set on the order of the last long name entry, which is stored first
+const LAST_LONG_ENTRY: u8 = 0x40;
+/// characters held by a long name entry
+const LONG_NAME_CHARS: usize = 13;
+/// offsets of the characters in a long name entry
+const LONG_NAME_OFFSETS: [usize; LONG_NAME_CHARS] = [1, 3, 5, 7, 9, 14, 16, 18, 20, 22, 24, 28, 30];
+pub const MAX_NAME_LEN: usize = 255;
+
+/// bits of the reserved byte telling the base and extension are lowercase
+pub const NT_LOWER_BASE: u8 = 0x08;
+pub const NT_LOWER_EXT: u8 = 0x10;
+
+/// 2000-01-01, there is no real time clock to date files with
+const DEFAULT_DATE: u16 = (20 << 9) | (1 << 5) | 1;
+
+/// The 8.3 entry of a file, the one holding its metadata
+#[derive(Clone, Copy)]
+pub struct ShortEntry(pub [u8; DIR_ENTRY_SIZE]);
+
+impl ShortEntry {
+    pub fn new(name: &[u8; 11], attr: u8, first_cluster: u32) -> Self {
+        let mut raw = [0u8; DIR_ENTRY_SIZE];
+        raw[..11].copy_from_slice(name);
+        raw[11] = attr;
+        for offset in [16, 18, 24] {
+            raw[offset..offset + 2].copy_from_slice(&DEFAULT_DATE.to_le_bytes());
+        }
+        let mut entry = Self(raw);
+        entry.set_first_cluster(first_cluster);
+        entry
+    }
+
+    pub fn attr(&self) -> u8 {
+        self.0[11]
+    }
+
+    pub fn is_dir(&self) -> bool {
+        self.attr() & ATTR_DIRECTORY != 0
+    }
+
+    pub fn first_cluster(&self) -> u32 {
+        let high = u16::from_le_bytes([self.0[20], self.0[21]]) as u32;
+        let low = u16::from_le_bytes([self.0[26], self.0[27]]) as u32;
+        (high << 16) | low
+    }
+
+    pub fn set_first_cluster(&mut self, cluster: u32) {
+        self.0[20..22].copy_from_slice(&((cluster >> 16) as u16).to_le_bytes());
+        self.0[26..28].copy_from_slice(&(cluster as u16).to_le_bytes());
+    }
+
+    pub fn size(&self) -> u32 {
+        u32::from_le_bytes([self.0[28], self.0[29], self.0[30], self.0[31]])
+    }
+
+    pub fn set_size(&mut self, size: u32) {
+        self.0[28..32].copy_from_slice(&size.to_le_bytes());
+    }
+
+    /// the 8.3 name as a file name, "." between base and extension
+    pub fn name(&self) -> String {
+        let lower_base = self.0[12] & NT_LOWER_BASE != 0;
+        let lower_ext = self.0[12] & NT_LOWER_EXT != 0;
+        let mut name = String::new();
+        for (i, &c) in self.0[..11].iter().enumerate() {
+            if i == 8 && self.0[8] != b' ' {
+                name.push('.');
+            }
+            // 0x05 stands for a real 0xe5 first character
+            let c = if i == 0 && c == 0x05 { ENTRY_FREE } else { c };
+            if c == b' ' {
+                continue;
+            }
+            let lower = if i < 8 { lower_base } else { lower_ext };
+            let c = if lower { c.to_ascii_lowercase() } else { c };
+            name.push(c as char);
+        }
+        name
+    }
+}
+
+/// checksum of a short name, stored in each of its long name entries
+pub fn checksum(short_name: &[u8]) -> u8 {
+    short_name[..11]
+        .iter()
+        .fold(0u8, |sum, &c| sum.rotate_right(1).wrapping_add(c))
+}
+
+/// order number of a long name entry, 1 for the one next to the short entry
+pub fn long_entry_order(raw: &[u8]) -> usize {
+    (raw[0] & !LAST_LONG_ENTRY) as usize
+}
+
+pub fn is_last_long_entry(raw: &[u8]) -> bool {
+    raw[0] & LAST_LONG_ENTRY != 0
+}
+
+pub fn long_entry_checksum(raw: &[u8]) -> u8 {
+    raw[13]
+}
+
+/// characters of a long name entry, up to the terminating nul
+pub fn long_entry_chars(raw: &[u8]) -> Vec<u16> {
+    LONG_NAME_OFFSETS
+        .iter()
+        .map(|&offset| u16::from_le_bytes([raw[offset], raw[offset + 1]]))
+        .take_while(|&c| c != 0)
+        .collect()
+}
+
+/// Long name entries of `name`, in on-disk order, to be followed by the short entry.
+pub fn long_entries(name: &str, checksum: u8) -> Vec<[u8; DIR_ENTRY_SIZE]> {
+    let chars: Vec<u16> = name.encode_utf16().collect();
+    let count = (chars.len() + LONG_NAME_CHARS - 1) / LONG_NAME_CHARS;
+    (0..count)
+        .rev()
+        .map(|i| {
+            let mut raw = [0u8; DIR_ENTRY_SIZE];
+            raw[0] = (i + 1) as u8;
+            if i == count - 1 {
+                raw[0] |= LAST_LONG_ENTRY;
+            }
+            raw[11] = ATTR_LONG_NAME;
+            raw[13] = checksum;
+            for (j, &offset) in LONG_NAME_OFFSETS.iter().enumerate() {
+                // a nul follows the name, the rest is padded with 0xffff
+                let c = match chars.get(i * LONG_NAME_CHARS + j) {
+                    Some(&c) => c,
+                    None if i * LONG_NAME_CHARS + j == chars.len() => 0,
+                    None => 0xffff,
+                };
+                raw[offset..offset + 2].copy_from_slice(&c.to_le_bytes());
+            }
+            raw
+        })
+        .collect()
+}
+
+fn is_short_name_char(c: u8) -> bool {
+    c.is_ascii_uppercase() || c.is_ascii_digit() || b"$%'-_@~`!(){}^#&".contains(&c)
+}
+
+/// Return the 8.3 name if `name` can be stored as one without a long name.
+pub fn exact_short_name(name: &str) -> Option<[u8; 11]> {
+    let (base, ext) = match name.rsplit_once('.') {
+        Some((base, ext)) => (base, ext),
+        None => (name, ""),
+    };
+    if base.is_empty()
+        || base.len() > 8
+        || ext.len() > 3
+        || !base.bytes().chain(ext.bytes()).all(is_short_name_char)
+    {
+        return None;
+    }
+    let mut short_name = [b' '; 11];
+    short_name[..base.len()].copy_from_slice(base.as_bytes());
+    short_name[8..8 + ext.len()].copy_from_slice(ext.as_bytes());
+    Some(short_name)
+}
+
+/// The `n`th candidate 8.3 alias of a long name, like "LONGNA~1TXT".
+pub fn short_name_alias(name: &str, n: usize) -> [u8; 11] {
+    let (base, ext) = match name.rsplit_once('.') {
+        Some((base, ext)) if !base.is_empty() => (base, ext),
+        _ => (name, ""),
+    };
+    let convert = |part: &str, max: usize| -> Vec<u8> {
+        part.bytes()
+            .filter(|&c| c != b' ' && c != b'.')
+            .map(|c| {
+                let c = c.to_ascii_uppercase();
+                if is_short_name_char(c) {
+                    c
+                } else {
+                    b'_'
+                }
+            })
+            .take(max)
+            .collect()
+    };
+    let tail = alloc::format!("~{}", n);
+    let base = convert(base, 8 - tail.len());
+    let ext = convert(ext, 3);
+    let mut short_name = [b' '; 11];
+    short_name[..base.len()].copy_from_slice(&base);
+    short_name[base.len()..base.len() + tail.len()].copy_from_slice(tail.as_bytes());
+    short_name[8..8 + ext.len()].copy_from_slice(&ext);
+    short_name
+}
+
+/// whether `name` may be a file name on FAT
+pub fn is_valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name != "."
+        && name != ".."
+        && name.encode_utf16().count() <= MAX_NAME_LEN
+        && !name
+            .chars()
+            .any(|c| (c as u32) < 0x20 || "\"*/:<>?\\|".contains(c))
+}
@@ -0,0 +1,564 @@
+//! FAT32, readable and writable, so images made with mkfs.vfat and mtools can be shared
+//! with the host
+//!
+//! Every inode refers to its short directory entry on the volume, which is read again on
+//! each access, so several inodes of the same file stay consistent.
+mod bpb;
+mod dir;
+
+use self::bpb::{is_fs_info, BiosParameterBlock, FSINFO_FREE_COUNT};
+use self::dir::*;
+use crate::drivers::block::{read_bytes, write_bytes, BlockDevice, BLOCK_SIZE};
+use crate::fs::{Inode, Stat, StatMode};
+use crate::sync::upsafecell::UPSafeCell;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::any::Any;
+use core::cmp::min;
+
+const FAT_ENTRY_MASK: u32 = 0x0fff_ffff;
+/// end of chain marker written by us, any value from `FAT_EOC_MIN` ends a chain
+const FAT_EOC: u32 = 0x0fff_ffff;
+
+pub struct Fat32FileSystem {
+    device: Arc<dyn BlockDevice>,
+    bpb: BiosParameterBlock,
+    /// cluster to start looking for a free one from
+    next_free: UPSafeCell<u32>,
+    /// offset of the FS information sector, until its free cluster count is marked unknown
+    fs_info: UPSafeCell<Option<usize>>,
+}
+
+impl Fat32FileSystem {
+    fn read_bytes(&self, offset: usize, buf: &mut [u8]) {
+        read_bytes(self.device.as_ref(), offset, buf);
+    }
+
+    fn write_bytes(&self, offset: usize, buf: &[u8]) {
+        write_bytes(self.device.as_ref(), offset, buf);
+    }
+
+    fn cluster_size(&self) -> usize {
+        self.bpb.cluster_size()
+    }
+
+    /// byte offset of `cluster` on the volume
+    fn cluster_offset(&self, cluster: u32) -> usize {
+        let sector =
+            self.bpb.data_start_sector() + (cluster as usize - 2) * self.bpb.sectors_per_cluster;
+        sector * self.bpb.bytes_per_sector
+    }
+
+    fn is_valid_cluster(&self, cluster: u32) -> bool {
+        cluster >= 2 && (cluster as usize) < self.bpb.cluster_count() + 2
+    }
+
+    fn fat_entry_offset(&self, fat: usize, cluster: u32) -> usize {
+        (self.bpb.fat_start_sector() + fat * self.bpb.fat_size) * self.bpb.bytes_per_sector
+            + cluster as usize * 4
+    }
+
+    fn fat_entry(&self, cluster: u32) -> u32 {
+        let mut raw = [0u8; 4];
+        self.read_bytes(self.fat_entry_offset(0, cluster), &mut raw);
+        u32::from_le_bytes(raw) & FAT_ENTRY_MASK
+    }
+
+    /// update every copy of the FAT, the reserved high bits are kept
+    fn set_fat_entry(&self, cluster: u32, value: u32) {
+        for fat in 0..self.bpb.num_fats {
+            let offset = self.fat_entry_offset(fat, cluster);
+            let mut raw = [0u8; 4];
+            self.read_bytes(offset, &mut raw);
+            let entry = (u32::from_le_bytes(raw) & !FAT_ENTRY_MASK) | (value & FAT_ENTRY_MASK);
+            self.write_bytes(offset, &entry.to_le_bytes());
+        }
+    }
+
+    /// the free cluster count is not kept up to date, mark it unknown the first time the
+    /// volume allocates or frees a cluster
+    fn invalidate_free_count(&self) {
+        let fs_info = self.fs_info.exclusive_access().take();
+        if let Some(offset) = fs_info {
+            self.write_bytes(offset + FSINFO_FREE_COUNT, &u32::MAX.to_le_bytes());
+        }
+    }
+
+    /// clusters of the chain starting at `first`, empty if `first` is 0
+    fn chain(&self, first: u32) -> Vec<u32> {
+        let mut chain = Vec::new();
+        let mut cluster = first;
+        // a corrupted FAT may loop
+        while self.is_valid_cluster(cluster) && chain.len() < self.bpb.cluster_count() {
+            chain.push(cluster);
+            cluster = self.fat_entry(cluster);
+        }
+        chain
+    }
+
+    /// find a free cluster, zero it and mark it as the end of a chain
+    fn alloc_cluster(&self) -> Option<u32> {
+        let count = self.bpb.cluster_count() as u32;
+        let start = *self.next_free.exclusive_access();
+        let cluster = (0..count)
+            .map(|i| (start - 2 + i) % count + 2)
+            .find(|&cluster| self.fat_entry(cluster) == 0)?;
+        self.invalidate_free_count();
+        self.set_fat_entry(cluster, FAT_EOC);
+        self.write_bytes(
+            self.cluster_offset(cluster),
+            &vec![0u8; self.cluster_size()],
+        );
+        *self.next_free.exclusive_access() = (cluster + 1 - 2) % count + 2;
+        Some(cluster)
+    }
+
+    fn free_chain(&self, first: u32) {
+        let chain = self.chain(first);
+        if !chain.is_empty() {
+            self.invalidate_free_count();
+        }
+        for cluster in chain {
+            self.set_fat_entry(cluster, 0);
+        }
+    }
+
+    /// Grow the chain starting at `first`, 0 for none, to at least `count` clusters.
+    /// Return its first cluster, or None if the volume is full, leaving the chain as it was.
+    fn extend_chain(&self, first: u32, count: usize) -> Option<u32> {
+        let chain = self.chain(first);
+        let mut allocated = Vec::new();
+        while chain.len() + allocated.len() < count {
+            match self.alloc_cluster() {
+                Some(cluster) => allocated.push(cluster),
+                None => {
+                    for cluster in allocated {
+                        self.set_fat_entry(cluster, 0);
+                    }
+                    return None;
+                }
+            }
+        }
+        let mut prev = chain.last().copied();
+        for &cluster in allocated.iter() {
+            if let Some(prev) = prev {
+                self.set_fat_entry(prev, cluster);
+            }
+            prev = Some(cluster);
+        }
+        Some(chain.first().or(allocated.first()).copied().unwrap_or(0))
+    }
+
+    /// read the chain starting at `first` from `offset`, up to its end
+    fn read_chain(&self, first: u32, offset: usize, buf: &mut [u8]) -> usize {
+        let cluster_size = self.cluster_size();
+        let chain = self.chain(first);
+        let mut done = 0;
+        while done < buf.len() {
+            let pos = offset + done;
+            let cluster = match chain.get(pos / cluster_size) {
+                Some(&cluster) => cluster,
+                None => break,
+            };
+            let size = min(cluster_size - pos % cluster_size, buf.len() - done);
+            self.read_bytes(
+                self.cluster_offset(cluster) + pos % cluster_size,
+                &mut buf[done..done + size],
+            );
+            done += size;
+        }
+        done
+    }
+
+    /// write the chain starting at `first` from `offset`, up to its end
+    fn write_chain(&self, first: u32, offset: usize, buf: &[u8]) -> usize {
+        let cluster_size = self.cluster_size();
+        let chain = self.chain(first);
+        let mut done = 0;
+        while done < buf.len() {
+            let pos = offset + done;
+            let cluster = match chain.get(pos / cluster_size) {
+                Some(&cluster) => cluster,
+                None => break,
+            };
+            let size = min(cluster_size - pos % cluster_size, buf.len() - done);
+            self.write_bytes(
+                self.cluster_offset(cluster) + pos % cluster_size,
+                &buf[done..done + size],
+            );
+            done += size;
+        }
+        done
+    }
+}
+
+/// An entry of a directory, with its long name resolved
+struct DirItem {
+    name: String,
+    entry: ShortEntry,
+    /// volume offsets of its long name entries and, last, of its short entry
+    slots: Vec<usize>,
+}
+
+impl DirItem {
+    fn entry_offset(&self) -> usize {
+        *self.slots.last().unwrap()
+    }
+}
+
+pub struct Fat32Inode {
+    fs: Arc<Fat32FileSystem>,
+    /// volume offset of the short entry, None for the root directory, which has none
+    entry_offset: Option<usize>,
+}
+
+impl Fat32Inode {
+    fn new(fs: Arc<Fat32FileSystem>, entry_offset: Option<usize>) -> Arc<dyn Inode> {
+        Arc::new(Self { fs, entry_offset })
+    }
+
+    fn entry(&self) -> ShortEntry {
+        match self.entry_offset {
+            Some(offset) => {
+                let mut raw = [0u8; DIR_ENTRY_SIZE];
+                self.fs.read_bytes(offset, &mut raw);
+                ShortEntry(raw)
+            }
+            None => ShortEntry::new(&[b' '; 11], ATTR_DIRECTORY, self.fs.bpb.root_cluster),
+        }
+    }
+
+    fn set_entry(&self, entry: &ShortEntry) {
+        if let Some(offset) = self.entry_offset {
+            self.fs.write_bytes(offset, &entry.0);
+        }
+    }
+
+    /// cluster a ".." entry refers to, 0 stands for the root
+    fn dotdot_cluster(&self) -> u32 {
+        match self.entry_offset {
+            Some(_) => self.entry().first_cluster(),
+            None => 0,
+        }
+    }
+
+    /// volume offset and first byte of every entry slot of this directory
+    fn dir_slots(&self) -> Vec<(usize, u8)> {
+        let fs = &self.fs;
+        let mut slots = Vec::new();
+        let mut data = vec![0u8; fs.cluster_size()];
+        for cluster in fs.chain(self.entry().first_cluster()) {
+            fs.read_bytes(fs.cluster_offset(cluster), &mut data);
+            for (i, raw) in data.chunks(DIR_ENTRY_SIZE).enumerate() {
+                slots.push((fs.cluster_offset(cluster) + i * DIR_ENTRY_SIZE, raw[0]));
+            }
+        }
+        slots
+    }
+
+    /// the entries of this directory except "." and ".."
+    fn dir_items(&self) -> Vec<DirItem> {
+        let fs = &self.fs;
+        let mut items = Vec::new();
+        let mut data = vec![0u8; fs.cluster_size()];
+        // long name entries seen so far, in on-disk order
+        let mut long_parts: Vec<Vec<u16>> = Vec::new();
+        let mut long_slots = Vec::new();
+        let mut long_checksum = 0;
+        let mut expected_order = 0;
+        for cluster in fs.chain(self.entry().first_cluster()) {
+            let cluster_offset = fs.cluster_offset(cluster);
+            fs.read_bytes(cluster_offset, &mut data);
+            for (i, raw) in data.chunks(DIR_ENTRY_SIZE).enumerate() {
+                let offset = cluster_offset + i * DIR_ENTRY_SIZE;
+                if raw[0] == ENTRY_END {
+                    return items;
+                }
+                if raw[0] == ENTRY_FREE {
+                    long_parts.clear();
+                    long_slots.clear();
+                    continue;
+                }
+                if raw[11] & ATTR_LONG_NAME == ATTR_LONG_NAME {
+                    let order = long_entry_order(raw);
+                    if is_last_long_entry(raw) {
+                        long_parts.clear();
+                        long_slots.clear();
+                        long_checksum = long_entry_checksum(raw);
+                    } else if order + 1 != expected_order
+                        || long_entry_checksum(raw) != long_checksum
+                    {
+                        long_parts.clear();
+                        long_slots.clear();
+                        continue;
+                    }
+                    expected_order = order;
+                    long_parts.push(long_entry_chars(raw));
+                    long_slots.push(offset);
+                    continue;
+                }
+                if raw[11] & ATTR_VOLUME_ID != 0 {
+                    long_parts.clear();
+                    long_slots.clear();
+                    continue;
+                }
+                let mut entry = ShortEntry([0; DIR_ENTRY_SIZE]);
+                entry.0.copy_from_slice(raw);
+                let has_long_name =
+                    !long_parts.is_empty() && expected_order == 1 && checksum(raw) == long_checksum;
+                let name = if has_long_name {
+                    let chars: Vec<u16> = long_parts.iter().rev().flatten().copied().collect();
+                    String::from_utf16_lossy(&chars)
+                } else {
+                    long_slots.clear();
+                    entry.name()
+                };
+                long_parts.clear();
+                let mut slots = core::mem::take(&mut long_slots);
+                slots.push(offset);
+                if name != "." && name != ".." {
+                    items.push(DirItem { name, entry, slots });
+                }
+            }
+        }
+        items
+    }
+
+    fn find_item(&self, name: &str) -> Option<DirItem> {
+        self.dir_items()
+            .into_iter()
+            .find(|item| item.name.eq_ignore_ascii_case(name))
+    }
+
+    /// short name and reserved byte for a new entry, and whether it needs a long name
+    fn new_short_name(&self, name: &str, items: &[DirItem]) -> ([u8; 11], u8, bool) {
+        let taken = |short_name: &[u8; 11]| {
+            items
+                .iter()
+                .any(|item| item.entry.0[..11] == short_name[..])
+        };
+        if let Some(short_name) = exact_short_name(name) {
+            return (short_name, 0, false);
+        }
+        // lowercase names that fit 8.3 are flagged instead of getting a long name
+        if name == name.to_ascii_lowercase() {
+            if let Some(short_name) = exact_short_name(&name.to_ascii_uppercase()) {
+                if !taken(&short_name) {
+                    return (short_name, NT_LOWER_BASE | NT_LOWER_EXT, false);
+                }
+            }
+        }
+        let short_name = (1..)
+            .map(|n| short_name_alias(name, n))
+            .find(|short_name| !taken(short_name))
+            .unwrap();
+        (short_name, 0, true)
+    }
+
+    /// Write `entries` in a run of free slots, growing the directory if needed.
+    /// Return the volume offset of the last one.
+    fn add_entries(&self, entries: &[[u8; DIR_ENTRY_SIZE]]) -> Option<usize> {
+        let find_run = |slots: &[(usize, u8)]| {
+            let mut run = 0;
+            for (i, &(_, first)) in slots.iter().enumerate() {
+                if first == ENTRY_END || first == ENTRY_FREE {
+                    run += 1;
+                    if run == entries.len() {
+                        return Some(i + 1 - run);
+                    }
+                } else {
+                    run = 0;
+                }
+            }
+            None
+        };
+        let mut slots = self.dir_slots();
+        let start = match find_run(&slots) {
+            Some(start) => start,
+            None => {
+                let needed = slots.len() + entries.len();
+                let clusters =
+                    (needed * DIR_ENTRY_SIZE + self.fs.cluster_size() - 1) / self.fs.cluster_size();
+                self.fs
+                    .extend_chain(self.entry().first_cluster(), clusters)?;
+                slots = self.dir_slots();
+                find_run(&slots)?
+            }
+        };
+        for (raw, &(offset, _)) in entries.iter().zip(slots[start..].iter()) {
+            self.fs.write_bytes(offset, raw);
+        }
+        Some(slots[start + entries.len() - 1].0)
+    }
+}
+
+impl Inode for Fat32Inode {
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> usize {
+        let entry = self.entry();
+        if entry.is_dir() || offset >= entry.size() as usize {
+            return 0;
+        }
+        let len = min(buf.len(), entry.size() as usize - offset);
+        self.fs
+            .read_chain(entry.first_cluster(), offset, &mut buf[..len])
+    }
+
+    fn write_at(&self, offset: usize, buf: &[u8]) -> usize {
+        let mut entry = self.entry();
+        let end = match offset.checked_add(buf.len()) {
+            Some(end) if end <= u32::MAX as usize => end,
+            _ => return 0,
+        };
+        if entry.is_dir() {
+            return 0;
+        }
+        let clusters = (end + self.fs.cluster_size() - 1) / self.fs.cluster_size();
+        let first = match self.fs.extend_chain(entry.first_cluster(), clusters) {
+            Some(first) => first,
+            None => return 0,
+        };
+        let written = self.fs.write_chain(first, offset, buf);
+        entry.set_first_cluster(first);
+        if end > entry.size() as usize {
+            entry.set_size(end as u32);
+        }
+        self.set_entry(&entry);
+        written
+    }
+
+    fn stat(&self) -> Stat {
+        let entry = self.entry();
+        let mode = if entry.is_dir() {
+            StatMode::DIR
+        } else {
+            StatMode::FILE
+        };
+        let ino = match self.entry_offset {
+            Some(offset) => offset / DIR_ENTRY_SIZE,
+            None => 0,
+        };
+        Stat::new(ino as u64, mode, entry.size() as u64)
+    }
+
+    fn lookup(&self, name: &str) -> Option<Arc<dyn Inode>> {
+        if !self.entry().is_dir() {
+            return None;
+        }
+        let item = self.find_item(name)?;
+        Some(Fat32Inode::new(self.fs.clone(), Some(item.entry_offset())))
+    }
+
+    fn create(&self, name: &str, mode: StatMode) -> Option<Arc<dyn Inode>> {
+        if !self.entry().is_dir() || !is_valid_name(name) {
+            return None;
+        }
+        let items = self.dir_items();
+        if items
+            .iter()
+            .any(|item| item.name.eq_ignore_ascii_case(name))
+        {
+            return None;
+        }
+        let (short_name, case_flags, needs_long_name) = self.new_short_name(name, &items);
+
+        let is_dir = mode == StatMode::DIR;
+        let first_cluster = if is_dir {
+            let cluster = self.fs.alloc_cluster()?;
+            let dot = ShortEntry::new(b".          ", ATTR_DIRECTORY, cluster);
+            let dotdot = ShortEntry::new(b"..         ", ATTR_DIRECTORY, self.dotdot_cluster());
+            let offset = self.fs.cluster_offset(cluster);
+            self.fs.write_bytes(offset, &dot.0);
+            self.fs.write_bytes(offset + DIR_ENTRY_SIZE, &dotdot.0);
+            cluster
+        } else {
+            0
+        };
+        let attr = if is_dir { ATTR_DIRECTORY } else { ATTR_ARCHIVE };
+        let mut short_entry = ShortEntry::new(&short_name, attr, first_cluster);
+        short_entry.0[12] = case_flags;
+
+        let mut entries = Vec::new();
+        if needs_long_name {
+            entries.extend(long_entries(name, checksum(&short_name)));
+        }
+        entries.push(short_entry.0);
+        match self.add_entries(&entries) {
+            Some(offset) => Some(Fat32Inode::new(self.fs.clone(), Some(offset))),
+            None => {
+                self.fs.free_chain(first_cluster);
+                None
+            }
+        }
+    }
+
+    fn list(&self) -> Vec<String> {
+        if !self.entry().is_dir() {
+            return Vec::new();
+        }
+        self.dir_items().into_iter().map(|item| item.name).collect()
+    }
+
+    fn truncate(&self) -> bool {
+        let mut entry = self.entry();
+        if entry.is_dir() {
+            return false;
+        }
+        self.fs.free_chain(entry.first_cluster());
+        entry.set_first_cluster(0);
+        entry.set_size(0);
+        self.set_entry(&entry);
+        true
+    }
+
+    fn unlink(&self, name: &str) -> bool {
+        if !self.entry().is_dir() {
+            return false;
+        }
+        let item = match self.find_item(name) {
+            Some(item) => item,
+            None => return false,
+        };
+        if item.entry.is_dir() {
+            let dir = Fat32Inode {
+                fs: self.fs.clone(),
+                entry_offset: Some(item.entry_offset()),
+            };
+            if !dir.dir_items().is_empty() {
+                return false;
+            }
+        }
+        self.fs.free_chain(item.entry.first_cluster());
+        for &offset in item.slots.iter() {
+            self.fs.write_bytes(offset, &[ENTRY_FREE]);
+        }
+        true
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+/// Return the root directory of the FAT32 volume on `device`, None if it holds none.
+pub fn mount(device: Arc<dyn BlockDevice>) -> Option<Arc<dyn Inode>> {
+    let mut sector = [0u8; BLOCK_SIZE];
+    device.read_block(0, &mut sector);
+    let bpb = BiosParameterBlock::parse(&sector)?;
+    let mut fs_info = None;
+    if bpb.fs_info_sector != 0 {
+        let offset = bpb.fs_info_sector * bpb.bytes_per_sector;
+        read_bytes(device.as_ref(), offset, &mut sector);
+        if is_fs_info(&sector) {
+            fs_info = Some(offset);
+        }
+    }
+    let fs = Arc::new(Fat32FileSystem {
+        device,
+        bpb,
+        next_free: unsafe { UPSafeCell::new(2) },
+        fs_info: unsafe { UPSafeCell::new(fs_info) },
+    });
+    Some(Fat32Inode::new(fs, None))
+}
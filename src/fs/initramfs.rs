@@ -0,0 +1,151 @@
+//! The initramfs, a cpio archive in the "newc" format unpacked into a tmpfs at boot
+//!
+//! The archive is the one passed by the boot loader in `/chosen` of the device tree,
+//! or else the one embedded in the kernel by `bootloader.asm`.
+use crate::fs::tmpfs::TmpfsInode;
+use crate::fs::StatMode;
+use crate::hal::board;
+use crate::mm::page_table::frame::release_reserved_frames;
+use crate::println;
+use alloc::sync::Arc;
+
+const NEWC_MAGIC: &[u8] = b"070701";
+const HEADER_SIZE: usize = 110;
+/// name of the entry closing the archive
+const TRAILER: &str = "TRAILER!!!";
+
+/// indices of the 8 hex digits fields of a header, after the magic
+const FIELD_MODE: usize = 1;
+const FIELD_MTIME: usize = 5;
+const FIELD_FILESIZE: usize = 6;
+const FIELD_NAMESIZE: usize = 11;
+/// file type bits of the mode
+const S_IFMT: u32 = 0o170000;
+
+const fn align4(n: usize) -> usize {
+    (n + 3) & !3
+}
+
+/// A member of the archive
+pub struct CpioEntry<'a> {
+    pub name: &'a str,
+    pub mode: u32,
+    pub mtime: u32,
+    pub data: &'a [u8],
+}
+
+/// Walks the entries of a newc archive, stops at the trailer or at the first malformed header
+pub struct CpioReader<'a> {
+    archive: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> CpioReader<'a> {
+    pub fn new(archive: &'a [u8]) -> Self {
+        Self { archive, offset: 0 }
+    }
+
+    fn field(header: &[u8], index: usize) -> Option<u32> {
+        let start = NEWC_MAGIC.len() + index * 8;
+        let digits = core::str::from_utf8(header.get(start..start + 8)?).ok()?;
+        u32::from_str_radix(digits, 16).ok()
+    }
+}
+
+impl<'a> Iterator for CpioReader<'a> {
+    type Item = CpioEntry<'a>;
+
+    fn next(&mut self) -> Option<CpioEntry<'a>> {
+        let header = self.archive.get(self.offset..self.offset + HEADER_SIZE)?;
+        if &header[..NEWC_MAGIC.len()] != NEWC_MAGIC {
+            return None;
+        }
+        let name_size = Self::field(header, FIELD_NAMESIZE)? as usize;
+        let file_size = Self::field(header, FIELD_FILESIZE)? as usize;
+        // the name size counts the terminating nul
+        let name_start = self.offset + HEADER_SIZE;
+        let name = self
+            .archive
+            .get(name_start..name_start + name_size.checked_sub(1)?)?;
+        let name = core::str::from_utf8(name).ok()?;
+        if name == TRAILER {
+            return None;
+        }
+        let data_start = align4(name_start + name_size);
+        let data = self.archive.get(data_start..data_start + file_size)?;
+        self.offset = align4(data_start + file_size);
+        Some(CpioEntry {
+            name,
+            mode: Self::field(header, FIELD_MODE)?,
+            mtime: Self::field(header, FIELD_MTIME)?,
+            data,
+        })
+    }
+}
+
+/// Unpack `archive` into the directory `root`, missing parent directories are created.
+/// Return the number of entries unpacked, device nodes and the like are skipped.
+pub fn unpack(archive: &[u8], root: &Arc<TmpfsInode>) -> usize {
+    let mut count = 0;
+    for entry in CpioReader::new(archive) {
+        let mut components = entry
+            .name
+            .split('/')
+            .filter(|name| !name.is_empty() && *name != ".");
+        let name = match components.next_back() {
+            Some(name) => name,
+            None => continue,
+        };
+        let parent = match components.try_fold(root.clone(), |dir, name| dir.mkdir(name)) {
+            Some(parent) => parent,
+            None => {
+                println!("[kernel] initramfs: no directory for {}", entry.name);
+                continue;
+            }
+        };
+        let inode = match StatMode::from_bits_truncate(entry.mode & S_IFMT) {
+            StatMode::DIR => parent.mkdir(name),
+            StatMode::FILE => parent.create_file(name, entry.data),
+            StatMode::LNK => core::str::from_utf8(entry.data)
+                .ok()
+                .and_then(|target| parent.symlink(name, target)),
+            _ => continue,
+        };
+        match inode {
+            Some(inode) => {
+                inode.set_mtime(entry.mtime as u64);
+                count += 1;
+            }
+            None => println!("[kernel] initramfs: can not unpack {}", entry.name),
+        }
+    }
+    count
+}
+
+/// the archive handed over by the boot loader, or the embedded one
+fn archive() -> &'static [u8] {
+    if let Some((start, end)) = board::initrd() {
+        return unsafe { core::slice::from_raw_parts(start as *const u8, end - start) };
+    }
+    extern "C" {
+        fn _initramfs_start();
+        fn _initramfs_end();
+    }
+    let start = _initramfs_start as usize;
+    unsafe { core::slice::from_raw_parts(start as *const u8, _initramfs_end as usize - start) }
+}
+
+/// Return a new tmpfs holding the content of the initramfs.
+pub fn mount() -> Arc<TmpfsInode> {
+    let root = TmpfsInode::new_root();
+    let archive = archive();
+    let count = unpack(archive, &root);
+    println!(
+        "[kernel] initramfs: {} entries unpacked from {} bytes",
+        count,
+        archive.len()
+    );
+    // everything is in tmpfs now, the archive from the boot loader is not needed anymore
+    release_reserved_frames();
+    root
+}
@@ -0,0 +1,198 @@
+use crate::fs::path::{is_mount_point, join, lookup, lookup_parent};
+use crate::fs::{File, Inode, SeekFrom, Stat, StatMode, UserBuffer};
+use crate::sync::upsafecell::UPSafeCell;
+use crate::task::signal::{signal_pending, EINTR};
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use bitflags::*;
+
+bitflags! {
+    /// flags of sys_open
+    pub struct OpenFlags: u32 {
+        const RDONLY = 0;
+        const WRONLY = 1 << 0;
+        const RDWR = 1 << 1;
+        const CREATE = 1 << 6;
+        const TRUNC = 1 << 10;
+    }
+}
+
+impl OpenFlags {
+    /// return (readable, writable)
+    pub fn read_write(&self) -> (bool, bool) {
+        if self.is_empty() {
+            (true, false)
+        } else if self.contains(Self::WRONLY) {
+            (false, true)
+        } else {
+            (true, self.contains(Self::RDWR))
+        }
+    }
+}
+
+/// An opened inode with its own offset
+pub struct OSInode {
+    readable: bool,
+    writable: bool,
+    inner: UPSafeCell<OSInodeInner>,
+}
+
+pub struct OSInodeInner {
+    offset: usize,
+    inode: Arc<dyn Inode>,
+}
+
+impl OSInode {
+    pub fn new(readable: bool, writable: bool, inode: Arc<dyn Inode>) -> Self {
+        Self {
+            readable,
+            writable,
+            inner: unsafe { UPSafeCell::new(OSInodeInner { offset: 0, inode }) },
+        }
+    }
+
+    /// read from the current offset to the end of the file
+    pub fn read_all(&self) -> Vec<u8> {
+        let mut inner = self.inner.exclusive_access();
+        let mut buffer = [0u8; 512];
+        let mut data = Vec::new();
+        loop {
+            let len = inner.inode.read_at(inner.offset, &mut buffer);
+            if len == 0 {
+                break;
+            }
+            inner.offset += len;
+            data.extend_from_slice(&buffer[..len]);
+        }
+        data
+    }
+}
+
+impl File for OSInode {
+    fn readable(&self) -> bool {
+        self.readable
+    }
+
+    fn writable(&self) -> bool {
+        self.writable
+    }
+
+    /// The inode is read without holding `inner`, a device may block until data comes.
+    fn read(&self, mut buf: UserBuffer) -> usize {
+        let (inode, mut offset) = {
+            let inner = self.inner.exclusive_access();
+            (inner.inode.clone(), inner.offset)
+        };
+        let mut total_read_size = 0;
+        for slice in buf.buffers.iter_mut() {
+            let read_size = inode.read_at(offset, slice);
+            offset += read_size;
+            total_read_size += read_size;
+            // a short read is the end of the file, or all a device had for now
+            if read_size < slice.len() {
+                break;
+            }
+        }
+        self.inner.exclusive_access().offset = offset;
+        total_read_size
+    }
+
+    /// a device such as /dev/console gives up waiting when a signal comes
+    fn read_error(&self) -> isize {
+        if self.stat().mode == StatMode::CHR && signal_pending() {
+            EINTR
+        } else {
+            0
+        }
+    }
+
+    fn write(&self, buf: UserBuffer) -> usize {
+        let (inode, mut offset) = {
+            let inner = self.inner.exclusive_access();
+            (inner.inode.clone(), inner.offset)
+        };
+        let mut total_write_size = 0;
+        for slice in buf.buffers.iter() {
+            let write_size = inode.write_at(offset, slice);
+            offset += write_size;
+            total_write_size += write_size;
+            if write_size < slice.len() {
+                break;
+            }
+        }
+        self.inner.exclusive_access().offset = offset;
+        total_write_size
+    }
+
+    fn seek(&self, pos: SeekFrom) -> Option<usize> {
+        let mut inner = self.inner.exclusive_access();
+        let new_offset = match pos {
+            SeekFrom::Start(offset) => offset as isize,
+            SeekFrom::Current(offset) => (inner.offset as isize).checked_add(offset)?,
+            SeekFrom::End(offset) => (inner.inode.stat().size as isize).checked_add(offset)?,
+        };
+        if new_offset < 0 {
+            return None;
+        }
+        inner.offset = new_offset as usize;
+        Some(inner.offset)
+    }
+
+    fn stat(&self) -> Stat {
+        self.inner.exclusive_access().inode.stat()
+    }
+}
+
+/// Open the file at `path`, relative to `cwd`
+pub fn open_file(cwd: &str, path: &str, flags: OpenFlags) -> Option<Arc<OSInode>> {
+    let (readable, writable) = flags.read_write();
+    let inode = match lookup(cwd, path, true) {
+        Some((_, inode)) => inode,
+        None if flags.contains(OpenFlags::CREATE) => {
+            let (_, parent, name) = lookup_parent(cwd, path)?;
+            parent.create(name, StatMode::FILE)?
+        }
+        None => return None,
+    };
+    if flags.contains(OpenFlags::TRUNC) && !inode.truncate() {
+        return None;
+    }
+    Some(Arc::new(OSInode::new(readable, writable, inode)))
+}
+
+/// Remove the file or empty directory at `path`, relative to `cwd`
+pub fn unlink_file(cwd: &str, path: &str) -> bool {
+    match lookup_parent(cwd, path) {
+        Some((parent_path, parent, name)) if !is_mount_point(&join(&parent_path, name)) => {
+            parent.unlink(name)
+        }
+        _ => false,
+    }
+}
+
+/// Create the directory `path`, relative to `cwd`, its parent must exist
+pub fn make_dir(cwd: &str, path: &str) -> bool {
+    match lookup_parent(cwd, path) {
+        Some((_, parent, name)) => parent.create(name, StatMode::DIR).is_some(),
+        None => false,
+    }
+}
+
+/// Move the file or directory at `old_path` to `new_path`, both relative to `cwd` and on
+/// the same file system
+pub fn rename_file(cwd: &str, old_path: &str, new_path: &str) -> bool {
+    let (old_parent_path, old_parent, old_name) = match lookup_parent(cwd, old_path) {
+        Some(parent) => parent,
+        None => return false,
+    };
+    let (new_parent_path, new_parent, new_name) = match lookup_parent(cwd, new_path) {
+        Some(parent) => parent,
+        None => return false,
+    };
+    if is_mount_point(&join(&old_parent_path, old_name))
+        || is_mount_point(&join(&new_parent_path, new_name))
+    {
+        return false;
+    }
+    old_parent.rename(old_name, &new_parent, new_name)
+}
@@ -0,0 +1,233 @@
+//! Virtual file system layer
+//!
+//! An [`Inode`] is a file stored somewhere (in memory, on a disk, ...), a [`File`] is
+//! what a file descriptor refers to: an opened inode with its own offset, or a device.
+
+pub mod devfs;
+pub mod ext2;
+pub mod fat32;
+pub mod initramfs;
+pub mod inode;
+pub mod path;
+pub mod pipe;
+pub mod procfs;
+pub mod sfs;
+pub mod stdio;
+pub mod tmpfs;
+
+pub use inode::{make_dir, open_file, rename_file, unlink_file, OSInode, OpenFlags};
+pub use path::{lookup, mount, root_inode};
+pub use pipe::make_pipe;
+pub use stdio::{Stdin, Stdout};
+
+use crate::drivers::block::{block_device_name, cached_block_device, BlockDevice};
+use crate::fs::devfs::DevRoot;
+use crate::fs::procfs::ProcRoot;
+use crate::println;
+use alloc::format;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use bitflags::*;
+use core::any::Any;
+
+/// Byte buffers of user space, split on page boundaries
+pub struct UserBuffer {
+    pub buffers: Vec<&'static mut [u8]>,
+}
+
+impl UserBuffer {
+    pub fn new(buffers: Vec<&'static mut [u8]>) -> Self {
+        Self { buffers }
+    }
+
+    pub fn len(&self) -> usize {
+        self.buffers.iter().map(|buffer| buffer.len()).sum()
+    }
+
+    /// copy `data` into the buffer, return the number of bytes copied
+    pub fn write_bytes(&mut self, data: &[u8]) -> usize {
+        let mut copied = 0;
+        for buffer in self.buffers.iter_mut() {
+            if copied == data.len() {
+                break;
+            }
+            let size = core::cmp::min(buffer.len(), data.len() - copied);
+            buffer[..size].copy_from_slice(&data[copied..copied + size]);
+            copied += size;
+        }
+        copied
+    }
+}
+
+bitflags! {
+    /// file type bits of `Stat::mode`
+    pub struct StatMode: u32 {
+        const NULL = 0;
+        /// directory
+        const DIR = 0o040000;
+        /// pipe
+        const FIFO = 0o010000;
+        /// character device
+        const CHR = 0o020000;
+        /// block device
+        const BLK = 0o060000;
+        /// regular file
+        const FILE = 0o100000;
+        /// symbolic link
+        const LNK = 0o120000;
+    }
+}
+
+/// File status returned by fstat
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct Stat {
+    /// id of the device containing the file
+    pub dev: u64,
+    /// inode number
+    pub ino: u64,
+    /// file type
+    pub mode: StatMode,
+    /// number of hard links
+    pub nlink: u32,
+    /// size in bytes
+    pub size: u64,
+    /// seconds since the epoch of the last access, modification and status change,
+    /// 0 if unknown
+    pub atime: u64,
+    pub mtime: u64,
+    pub ctime: u64,
+}
+
+impl Stat {
+    pub fn new(ino: u64, mode: StatMode, size: u64) -> Self {
+        Self {
+            dev: 0,
+            ino,
+            mode,
+            nlink: 1,
+            size,
+            atime: 0,
+            mtime: 0,
+            ctime: 0,
+        }
+    }
+}
+
+/// Position used by `File::seek`
+#[derive(Debug, Clone, Copy)]
+pub enum SeekFrom {
+    Start(usize),
+    Current(isize),
+    End(isize),
+}
+
+/// A file stored in some file system, accessed by offset
+pub trait Inode: Send + Sync {
+    /// read from `offset` into `buf`, return the number of bytes read
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> usize;
+    /// write `buf` at `offset`, return the number of bytes written
+    fn write_at(&self, offset: usize, buf: &[u8]) -> usize;
+    /// file status
+    fn stat(&self) -> Stat;
+    /// find the entry `name` of a directory
+    fn lookup(&self, _name: &str) -> Option<Arc<dyn Inode>> {
+        None
+    }
+    /// create a regular file or a directory, as told by `mode`, in a directory
+    fn create(&self, _name: &str, _mode: StatMode) -> Option<Arc<dyn Inode>> {
+        None
+    }
+    /// names of the entries of a directory
+    fn list(&self) -> Vec<String> {
+        Vec::new()
+    }
+    /// drop the content of a regular file, return false if it can not be changed
+    fn truncate(&self) -> bool {
+        false
+    }
+    /// remove the entry `name` of a directory, directories must be empty
+    fn unlink(&self, _name: &str) -> bool {
+        false
+    }
+    /// target of a symbolic link
+    fn readlink(&self) -> Option<String> {
+        None
+    }
+    /// move the entry `old_name` of a directory to `new_name` in `new_dir`, on the same
+    /// file system, replacing a file or empty directory already there
+    fn rename(&self, _old_name: &str, _new_dir: &Arc<dyn Inode>, _new_name: &str) -> bool {
+        false
+    }
+    /// the concrete inode, for a file system to recognize its own ones
+    fn as_any(&self) -> &dyn Any;
+}
+
+/// What a file descriptor refers to
+pub trait File: Send + Sync {
+    fn readable(&self) -> bool;
+    fn writable(&self) -> bool;
+    /// read into user buffer, return the number of bytes read
+    fn read(&self, buf: UserBuffer) -> usize;
+    /// write from user buffer, return the number of bytes written
+    fn write(&self, buf: UserBuffer) -> usize;
+    /// what read returns when nothing could be read, 0 being the end of the file
+    fn read_error(&self) -> isize {
+        0
+    }
+    /// what write returns when nothing could be written
+    fn write_error(&self) -> isize {
+        -1
+    }
+    /// move the offset, return the new one or None if the file is not seekable
+    fn seek(&self, _pos: SeekFrom) -> Option<usize> {
+        None
+    }
+    /// file status
+    fn stat(&self) -> Stat;
+}
+
+type MountFn = fn(Arc<dyn BlockDevice>) -> Option<Arc<dyn Inode>>;
+
+/// disk file systems, tried in order on a block device
+const DISK_FILE_SYSTEMS: [(&str, MountFn); 3] = [
+    ("simple_fs", sfs::mount),
+    ("fat32", fat32::mount),
+    ("ext2", ext2::mount),
+];
+
+/// return the name and the root directory of the file system found on `device`
+fn mount_block_device(device: Arc<dyn BlockDevice>) -> Option<(&'static str, Arc<dyn Inode>)> {
+    DISK_FILE_SYSTEMS
+        .iter()
+        .find_map(|&(name, mount)| mount(device.clone()).map(|root| (name, root)))
+}
+
+/// Mount the initramfs on /, devfs on /dev, procfs on /proc, and every file system found on
+/// a block device on /mnt/<name of the device>.
+pub fn init() {
+    mount("/", initramfs::mount());
+    make_dir("/", "/dev");
+    mount("/dev", Arc::new(DevRoot));
+    make_dir("/", "/proc");
+    mount("/proc", Arc::new(ProcRoot));
+    let mut index = 0;
+    while let Some(device) = cached_block_device(index) {
+        if let Some((name, root)) = mount_block_device(device) {
+            let path = format!("/mnt/{}", block_device_name(index));
+            make_dir("/", "/mnt");
+            make_dir("/", &path);
+            if mount(&path, root) {
+                println!(
+                    "[kernel] mount {} of block device {} on {}",
+                    name, index, path
+                );
+            }
+        }
+        index += 1;
+    }
+    for name in root_inode().list() {
+        println!("[kernel] /{}", name);
+    }
+}
@@ -0,0 +1,161 @@
+//! Path resolution over the mount table
+//!
+//! A path is walked one component at a time from the root or the current directory. The
+//! directories walked through are kept, so ".." goes back across mount points, and a
+//! mount point is replaced by the root of the file system mounted on it.
+use crate::fs::{Inode, StatMode};
+use crate::sync::upsafecell::UPSafeCell;
+use alloc::collections::BTreeMap;
+use alloc::string::{String, ToString};
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+use lazy_static::*;
+
+/// symbolic links followed while resolving one path, more means a loop
+pub const MAX_SYMLINK_FOLLOWS: usize = 40;
+
+lazy_static! {
+    /// root directory of every mounted file system, by canonical path of its mount point
+    static ref MOUNTS: UPSafeCell<BTreeMap<String, Arc<dyn Inode>>> =
+        unsafe { UPSafeCell::new(BTreeMap::new()) };
+}
+
+/// the root of the file system mounted on `path`, a canonical path
+fn mounted_on(path: &str) -> Option<Arc<dyn Inode>> {
+    MOUNTS.exclusive_access().get(path).cloned()
+}
+
+pub fn root_inode() -> Arc<dyn Inode> {
+    mounted_on("/").expect("File system is not mounted!")
+}
+
+pub fn is_mount_point(path: &str) -> bool {
+    MOUNTS.exclusive_access().contains_key(path)
+}
+
+/// Mount `root` on the directory `path`, an absolute path. Nothing can be mounted on a
+/// mount point again, but "/" has to be mounted first.
+pub fn mount(path: &str, root: Arc<dyn Inode>) -> bool {
+    let path = if MOUNTS.exclusive_access().is_empty() {
+        if path != "/" {
+            return false;
+        }
+        String::from("/")
+    } else {
+        match lookup("/", path, true) {
+            Some((path, dir)) if dir.stat().mode == StatMode::DIR && !is_mount_point(&path) => path,
+            _ => return false,
+        }
+    };
+    MOUNTS.exclusive_access().insert(path, root);
+    true
+}
+
+/// The directories from the root to the current one
+struct Walk {
+    names: Vec<String>,
+    /// the root, then the inode of each name
+    inodes: Vec<Arc<dyn Inode>>,
+}
+
+impl Walk {
+    fn root() -> Self {
+        Self {
+            names: Vec::new(),
+            inodes: vec![root_inode()],
+        }
+    }
+
+    fn path(&self) -> String {
+        if self.names.is_empty() {
+            return String::from("/");
+        }
+        self.names
+            .iter()
+            .fold(String::new(), |path, name| path + "/" + name)
+    }
+
+    fn current(&self) -> &Arc<dyn Inode> {
+        self.inodes.last().unwrap()
+    }
+
+    /// go up, the parent of the root is the root
+    fn parent(&mut self) {
+        if self.names.pop().is_some() {
+            self.inodes.pop();
+        }
+    }
+
+    fn enter(&mut self, name: &str, inode: Arc<dyn Inode>) {
+        self.names.push(name.to_string());
+        let inode = mounted_on(&self.path()).unwrap_or(inode);
+        self.inodes.push(inode);
+    }
+}
+
+/// components of `path` to walk, last one first
+fn components(path: &str) -> impl DoubleEndedIterator<Item = &str> {
+    path.split('/')
+        .filter(|name| !name.is_empty() && *name != ".")
+}
+
+/// Resolve `path`, relative to `cwd` unless it is absolute, `cwd` being a canonical path.
+/// A symbolic link as last component is followed if `follow_last` is set.
+/// Return the canonical path and the inode it names.
+pub fn lookup(cwd: &str, path: &str, follow_last: bool) -> Option<(String, Arc<dyn Inode>)> {
+    let mut walk = Walk::root();
+    let mut pending: Vec<String> = components(path).rev().map(String::from).collect();
+    if !path.starts_with('/') {
+        pending.extend(components(cwd).rev().map(String::from));
+    }
+    let mut follows = 0;
+    while let Some(name) = pending.pop() {
+        if name == ".." {
+            walk.parent();
+            continue;
+        }
+        let inode = walk.current().lookup(&name)?;
+        let is_last = pending.is_empty();
+        if inode.stat().mode == StatMode::LNK && (follow_last || !is_last) {
+            follows += 1;
+            if follows > MAX_SYMLINK_FOLLOWS {
+                return None;
+            }
+            let target = inode.readlink()?;
+            if target.starts_with('/') {
+                walk = Walk::root();
+            }
+            pending.extend(components(&target).rev().map(String::from));
+        } else {
+            walk.enter(&name, inode);
+        }
+    }
+    Some((walk.path(), walk.current().clone()))
+}
+
+/// Resolve the directory holding the last component of `path`.
+/// Return its canonical path, its inode and the name of the last component, which is
+/// neither "." nor "..".
+pub fn lookup_parent<'a>(cwd: &str, path: &'a str) -> Option<(String, Arc<dyn Inode>, &'a str)> {
+    let trimmed = path.trim_end_matches('/');
+    let (dir, name) = match trimmed.rsplit_once('/') {
+        Some(("", name)) => ("/", name),
+        Some((dir, name)) => (dir, name),
+        None => (".", trimmed),
+    };
+    if name.is_empty() || name == "." || name == ".." {
+        return None;
+    }
+    let (dir_path, dir) = lookup(cwd, dir, true)?;
+    Some((dir_path, dir, name))
+}
+
+/// canonical path of the entry `name` of the directory `dir_path`
+pub fn join(dir_path: &str, name: &str) -> String {
+    if dir_path == "/" {
+        String::from("/") + name
+    } else {
+        String::from(dir_path) + "/" + name
+    }
+}
@@ -0,0 +1,179 @@
+//! Anonymous pipes, a ring buffer shared by a read end and a write end
+//!
+//! A reader blocks until there is data or the write end is closed, which reads as the end
+//! of the file. A writer blocks while the buffer is full, and stops as soon as the read end
+//! is closed, getting `EPIPE` and `SIGPIPE` if it wrote nothing. Both give up waiting when
+//! a signal comes, a reader getting `EINTR` if it read nothing.
+use crate::fs::{File, Stat, StatMode, UserBuffer};
+use crate::misc::ring_buffer::RingBuffer;
+use crate::sync::upsafecell::UPSafeCell;
+use crate::task::cpu::current_task;
+use crate::task::sche::WaitQueue;
+use crate::task::signal::{send_signal, signal_pending, SignalFlags, EINTR};
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+/// bytes a pipe holds before writers block, kept small as the buffer is built on the
+/// kernel stack
+const PIPE_BUFFER_SIZE: usize = 512;
+
+/// return value of a write to a pipe whose read end is closed
+pub const EPIPE: isize = -32;
+
+struct PipeBuffer {
+    ring: RingBuffer<PIPE_BUFFER_SIZE>,
+    read_closed: bool,
+    write_closed: bool,
+}
+
+struct PipeShared {
+    buffer: UPSafeCell<PipeBuffer>,
+    /// readers waiting for data
+    readers: WaitQueue,
+    /// writers waiting for room
+    writers: WaitQueue,
+}
+
+/// One end of a pipe. Each end is a single file shared by every descriptor referring to
+/// it, so it is closed when the last of them is.
+pub struct Pipe {
+    writable: bool,
+    shared: Arc<PipeShared>,
+}
+
+/// Return the read end and the write end of a new pipe.
+pub fn make_pipe() -> (Arc<Pipe>, Arc<Pipe>) {
+    let shared = Arc::new(PipeShared {
+        buffer: unsafe {
+            UPSafeCell::new(PipeBuffer {
+                ring: RingBuffer::new(),
+                read_closed: false,
+                write_closed: false,
+            })
+        },
+        readers: WaitQueue::new(),
+        writers: WaitQueue::new(),
+    });
+    let read_end = Arc::new(Pipe {
+        writable: false,
+        shared: shared.clone(),
+    });
+    let write_end = Arc::new(Pipe {
+        writable: true,
+        shared,
+    });
+    (read_end, write_end)
+}
+
+impl File for Pipe {
+    fn readable(&self) -> bool {
+        !self.writable
+    }
+
+    fn writable(&self) -> bool {
+        self.writable
+    }
+
+    /// Block until there is data, then take what is available.
+    /// Return 0 once the buffer is empty and the write end closed, or if a signal comes
+    /// first, which `read_error` tells apart.
+    fn read(&self, mut buf: UserBuffer) -> usize {
+        let len = buf.len();
+        if len == 0 {
+            return 0;
+        }
+        loop {
+            let mut buffer = self.shared.buffer.exclusive_access();
+            if buffer.ring.is_empty() {
+                if buffer.write_closed || signal_pending() {
+                    return 0;
+                }
+                drop(buffer);
+                self.shared.readers.wait();
+                continue;
+            }
+            let data: Vec<u8> = core::iter::from_fn(|| buffer.ring.pop())
+                .take(len)
+                .collect();
+            drop(buffer);
+            self.shared.writers.wake_all();
+            return buf.write_bytes(&data);
+        }
+    }
+
+    /// Block until everything is written. Return less once the read end is closed or a
+    /// signal comes, 0 if nothing could be written, in which case a closed read end sends
+    /// SIGPIPE to the writer.
+    fn write(&self, buf: UserBuffer) -> usize {
+        let mut bytes = buf
+            .buffers
+            .iter()
+            .flat_map(|buffer| buffer.iter().copied())
+            .peekable();
+        let mut written = 0;
+        loop {
+            let mut buffer = self.shared.buffer.exclusive_access();
+            if buffer.read_closed {
+                if written == 0 {
+                    drop(buffer);
+                    send_signal(
+                        &current_task().expect("No current task."),
+                        SignalFlags::SIGPIPE,
+                    );
+                }
+                break;
+            }
+            while let Some(&byte) = bytes.peek() {
+                if !buffer.ring.push(byte) {
+                    break;
+                }
+                bytes.next();
+                written += 1;
+            }
+            drop(buffer);
+            self.shared.readers.wake_all();
+            if bytes.peek().is_none() || signal_pending() {
+                break;
+            }
+            self.shared.writers.wait();
+        }
+        written
+    }
+
+    fn read_error(&self) -> isize {
+        let buffer = self.shared.buffer.exclusive_access();
+        if buffer.write_closed && buffer.ring.is_empty() {
+            0
+        } else {
+            EINTR
+        }
+    }
+
+    fn write_error(&self) -> isize {
+        if self.shared.buffer.exclusive_access().read_closed {
+            EPIPE
+        } else {
+            -1
+        }
+    }
+
+    fn stat(&self) -> Stat {
+        let len = self.shared.buffer.exclusive_access().ring.len();
+        Stat::new(0, StatMode::FIFO, len as u64)
+    }
+}
+
+impl Drop for Pipe {
+    fn drop(&mut self) {
+        let mut buffer = self.shared.buffer.exclusive_access();
+        if self.writable {
+            buffer.write_closed = true;
+        } else {
+            buffer.read_closed = true;
+        }
+        drop(buffer);
+        // the tasks blocked on the other end have to see it
+        self.shared.readers.wake_all();
+        self.shared.writers.wake_all();
+    }
+}
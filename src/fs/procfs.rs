@@ -0,0 +1,242 @@
+//! procfs, read-only files generated from the state of the kernel when they are read
+//!
+//! /proc/meminfo      frames and kernel heap
+//! /proc/uptime       seconds since boot
+//! /proc/<pid>/status state, parent, exit code and memory of a task
+//! /proc/<pid>/maps   segments of the address space of a task
+use crate::fs::{Inode, Stat, StatMode};
+use crate::hal::syscall::get_time_ms;
+use crate::hal::*;
+use crate::mm::heap_allocator::heap_stats;
+use crate::mm::page_table::frame::frame_stats;
+use crate::sysconfig::PAGE_SIZE;
+use crate::task::task::{TaskControlBlock, TaskStatus};
+use crate::task::{all_tasks, find_task};
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::any::Any;
+use core::cmp::min;
+use core::fmt::Write;
+
+const ROOT_INO: u64 = 1;
+const KB: usize = 1024;
+
+#[derive(Copy, Clone)]
+enum ProcFileKind {
+    MemInfo,
+    Uptime,
+    Status(usize),
+    Maps(usize),
+}
+
+impl ProcFileKind {
+    fn ino(&self) -> u64 {
+        match *self {
+            Self::MemInfo => 2,
+            Self::Uptime => 3,
+            Self::Status(pid) => pid_ino(pid) + 1,
+            Self::Maps(pid) => pid_ino(pid) + 2,
+        }
+    }
+
+    /// the content, None once the task is gone
+    fn generate(&self) -> Option<String> {
+        match *self {
+            Self::MemInfo => Some(meminfo()),
+            Self::Uptime => {
+                let ms = get_time_ms();
+                Some(format!("{}.{:02}\n", ms / 1000, ms % 1000 / 10))
+            }
+            Self::Status(pid) => find_task(pid).map(|task| status(&task)),
+            Self::Maps(pid) => find_task(pid).map(|task| maps(&task)),
+        }
+    }
+}
+
+/// inode number of the directory of a task, its files follow it
+fn pid_ino(pid: usize) -> u64 {
+    ((pid as u64) + 1) << 4
+}
+
+fn meminfo() -> String {
+    let (frames, free_frames) = frame_stats();
+    let (heap_total, heap_used) = heap_stats();
+    format!(
+        "MemTotal:  {:>8} kB\nMemFree:   {:>8} kB\nHeapTotal: {:>8} kB\nHeapUsed:  {:>8} kB\n",
+        frames * PAGE_SIZE / KB,
+        free_frames * PAGE_SIZE / KB,
+        heap_total / KB,
+        heap_used / KB,
+    )
+}
+
+fn status(task: &Arc<TaskControlBlock>) -> String {
+    let inner = task.inner_exclusive_access();
+    let state = match inner.status {
+        TaskStatus::Ready => "R (ready)",
+        TaskStatus::Running => "R (running)",
+        TaskStatus::UnInit => "U (uninit)",
+        TaskStatus::Zombie => "Z (zombie)",
+        TaskStatus::Blocked => "S (sleeping)",
+    };
+    let ppid = inner
+        .parent
+        .as_ref()
+        .and_then(|parent| parent.upgrade())
+        .map_or(0, |parent| parent.get_pid());
+    format!(
+        "Pid:\t{}\nPPid:\t{}\nState:\t{}\nExitCode:\t{}\nCwd:\t{}\nChildren:\t{}\nFDs:\t{}\n\
+         SigPnd:\t{:08x}\nSigBlk:\t{:08x}\nVmSize:\t{} kB\nVmRSS:\t{} kB\n",
+        task.get_pid(),
+        ppid,
+        state,
+        inner.exit_code,
+        inner.cwd,
+        inner.childern.len(),
+        inner.fd_table.iter().flatten().count(),
+        inner.signals.bits(),
+        inner.signal_mask.bits(),
+        inner.memory_set.reserved_pages() * PAGE_SIZE / KB,
+        inner.memory_set.resident_pages() * PAGE_SIZE / KB,
+    )
+}
+
+fn maps(task: &Arc<TaskControlBlock>) -> String {
+    let inner = task.inner_exclusive_access();
+    let mut maps = String::new();
+    for (start, end, permission, map_type) in inner.memory_set.segment_ranges() {
+        let flag = |bit: MapPermission, c: char| if permission.contains(bit) { c } else { '-' };
+        writeln!(
+            maps,
+            "{:016x}-{:016x} {}{}{}{} {:?}",
+            usize::from(start),
+            usize::from(end),
+            flag(MapPermission::R, 'r'),
+            flag(MapPermission::W, 'w'),
+            flag(MapPermission::X, 'x'),
+            flag(MapPermission::U, 'u'),
+            map_type,
+        )
+        .unwrap();
+    }
+    maps
+}
+
+/// A file generated on each read
+struct ProcFile {
+    kind: ProcFileKind,
+}
+
+impl Inode for ProcFile {
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> usize {
+        let content = match self.kind.generate() {
+            Some(content) => content,
+            None => return 0,
+        };
+        let content = content.as_bytes();
+        if offset >= content.len() {
+            return 0;
+        }
+        let len = min(buf.len(), content.len() - offset);
+        buf[..len].copy_from_slice(&content[offset..offset + len]);
+        len
+    }
+
+    fn write_at(&self, _offset: usize, _buf: &[u8]) -> usize {
+        0
+    }
+
+    /// The size is the one of the content now, it may change by the next read.
+    fn stat(&self) -> Stat {
+        let size = self.kind.generate().map_or(0, |content| content.len());
+        Stat::new(self.kind.ino(), StatMode::FILE, size as u64)
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+fn proc_file(kind: ProcFileKind) -> Arc<dyn Inode> {
+    Arc::new(ProcFile { kind })
+}
+
+/// /proc/<pid>
+struct ProcPidDir {
+    pid: usize,
+}
+
+impl Inode for ProcPidDir {
+    fn read_at(&self, _offset: usize, _buf: &mut [u8]) -> usize {
+        0
+    }
+
+    fn write_at(&self, _offset: usize, _buf: &[u8]) -> usize {
+        0
+    }
+
+    fn stat(&self) -> Stat {
+        Stat::new(pid_ino(self.pid), StatMode::DIR, 0)
+    }
+
+    fn lookup(&self, name: &str) -> Option<Arc<dyn Inode>> {
+        find_task(self.pid)?;
+        match name {
+            "status" => Some(proc_file(ProcFileKind::Status(self.pid))),
+            "maps" => Some(proc_file(ProcFileKind::Maps(self.pid))),
+            _ => None,
+        }
+    }
+
+    fn list(&self) -> Vec<String> {
+        vec![String::from("maps"), String::from("status")]
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+/// The root of procfs, a directory per task next to the global files
+pub struct ProcRoot;
+
+impl Inode for ProcRoot {
+    fn read_at(&self, _offset: usize, _buf: &mut [u8]) -> usize {
+        0
+    }
+
+    fn write_at(&self, _offset: usize, _buf: &[u8]) -> usize {
+        0
+    }
+
+    fn stat(&self) -> Stat {
+        Stat::new(ROOT_INO, StatMode::DIR, 0)
+    }
+
+    fn lookup(&self, name: &str) -> Option<Arc<dyn Inode>> {
+        match name {
+            "meminfo" => Some(proc_file(ProcFileKind::MemInfo)),
+            "uptime" => Some(proc_file(ProcFileKind::Uptime)),
+            _ => {
+                let pid = name.parse().ok()?;
+                find_task(pid)?;
+                Some(Arc::new(ProcPidDir { pid }))
+            }
+        }
+    }
+
+    fn list(&self) -> Vec<String> {
+        let mut pids: Vec<usize> = all_tasks().iter().map(|task| task.get_pid()).collect();
+        pids.sort_unstable();
+        let mut names = vec![String::from("meminfo"), String::from("uptime")];
+        names.extend(pids.iter().map(|pid| pid.to_string()));
+        names
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
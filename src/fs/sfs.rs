@@ -0,0 +1,86 @@
+//! simple_fs, the on-disk file system built by `sfs_pack`
+use crate::drivers::block::{BlockDevice, BLOCK_SIZE};
+use crate::fs::{Inode, Stat, StatMode};
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::any::Any;
+use simple_fs::{DiskInodeType, SimpleFileSystem, BLOCK_SZ};
+
+/// Gives simple_fs access to a block device of the kernel
+struct SfsBlockDevice(Arc<dyn BlockDevice>);
+
+impl simple_fs::BlockDevice for SfsBlockDevice {
+    fn read_block(&self, block_id: usize, buf: &mut [u8]) {
+        self.0.read_block(block_id, buf);
+    }
+
+    fn write_block(&self, block_id: usize, buf: &[u8]) {
+        self.0.write_block(block_id, buf);
+    }
+}
+
+pub struct SfsInode {
+    inner: Arc<simple_fs::Inode>,
+}
+
+impl SfsInode {
+    fn new(inner: Arc<simple_fs::Inode>) -> Arc<dyn Inode> {
+        Arc::new(Self { inner })
+    }
+}
+
+/// Return the root directory of the simple_fs on `device`, None if it holds none.
+pub fn mount(device: Arc<dyn BlockDevice>) -> Option<Arc<dyn Inode>> {
+    assert_eq!(BLOCK_SZ, BLOCK_SIZE);
+    let fs = SimpleFileSystem::open(Arc::new(SfsBlockDevice(device)))?;
+    Some(SfsInode::new(Arc::new(SimpleFileSystem::root_inode(&fs))))
+}
+
+impl Inode for SfsInode {
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> usize {
+        self.inner.read_at(offset, buf)
+    }
+
+    fn write_at(&self, offset: usize, buf: &[u8]) -> usize {
+        self.inner.write_at(offset, buf)
+    }
+
+    fn stat(&self) -> Stat {
+        let mode = if self.inner.is_dir() {
+            StatMode::DIR
+        } else {
+            StatMode::FILE
+        };
+        Stat::new(self.inner.inode_id() as u64, mode, self.inner.size() as u64)
+    }
+
+    fn lookup(&self, name: &str) -> Option<Arc<dyn Inode>> {
+        self.inner.find(name).map(SfsInode::new)
+    }
+
+    fn create(&self, name: &str, mode: StatMode) -> Option<Arc<dyn Inode>> {
+        let type_ = if mode == StatMode::DIR {
+            DiskInodeType::Directory
+        } else {
+            DiskInodeType::File
+        };
+        self.inner.create(name, type_).map(SfsInode::new)
+    }
+
+    fn list(&self) -> Vec<String> {
+        self.inner.ls()
+    }
+
+    fn truncate(&self) -> bool {
+        if self.inner.is_dir() {
+            return false;
+        }
+        self.inner.clear();
+        true
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
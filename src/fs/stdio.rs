@@ -0,0 +1,65 @@
+use crate::drivers::chardev::console::read_console;
+use crate::fs::{File, Stat, StatMode, UserBuffer};
+use crate::hal::console::write_bytes;
+use crate::task::signal::EINTR;
+
+/// Console input
+pub struct Stdin;
+
+/// Console output, also used for stderr
+pub struct Stdout;
+
+impl File for Stdin {
+    fn readable(&self) -> bool {
+        true
+    }
+
+    fn writable(&self) -> bool {
+        false
+    }
+
+    /// block until at least one character arrives, then take what is available
+    fn read(&self, buf: UserBuffer) -> usize {
+        read_console(buf.buffers.into_iter().flatten())
+    }
+
+    /// the console never ends, reading nothing means a signal came first
+    fn read_error(&self) -> isize {
+        EINTR
+    }
+
+    fn write(&self, _buf: UserBuffer) -> usize {
+        panic!("Cannot write to stdin!");
+    }
+
+    fn stat(&self) -> Stat {
+        Stat::new(0, StatMode::CHR, 0)
+    }
+}
+
+impl File for Stdout {
+    fn readable(&self) -> bool {
+        false
+    }
+
+    fn writable(&self) -> bool {
+        true
+    }
+
+    fn read(&self, _buf: UserBuffer) -> usize {
+        panic!("Cannot read from stdout!");
+    }
+
+    /// the bytes go out as they are, a character may be split across buffers
+    fn write(&self, buf: UserBuffer) -> usize {
+        let len = buf.len();
+        for buffer in buf.buffers {
+            write_bytes(buffer);
+        }
+        len
+    }
+
+    fn stat(&self) -> Stat {
+        Stat::new(0, StatMode::CHR, 0)
+    }
+}
@@ -0,0 +1,346 @@
+//! tmpfs, a writable file system kept in memory, filled from the initramfs at boot
+//!
+//! The content of regular files lives in frames allocated for the pages written, a hole
+//! reads as zeros. Directories map names to inodes. Everything is lost at shutdown.
+use crate::fs::{Inode, Stat, StatMode};
+use crate::hal::rtc::epoch_secs;
+use crate::hal::*;
+use crate::mm::page_table::frame::{frame_alloc, FrameTracker};
+use crate::sync::upsafecell::UPSafeCell;
+use crate::sysconfig::PAGE_SIZE;
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::any::Any;
+use core::cmp::{max, min};
+use core::sync::atomic::{AtomicU64, Ordering};
+
+static NEXT_INO: AtomicU64 = AtomicU64::new(1);
+
+enum Content {
+    File {
+        /// frame of every page written, by page index
+        pages: BTreeMap<usize, FrameTracker>,
+        size: usize,
+    },
+    Dir(BTreeMap<String, Arc<TmpfsInode>>),
+    Symlink(String),
+}
+
+pub struct TmpfsInode {
+    ino: u64,
+    inner: UPSafeCell<TmpfsInodeInner>,
+}
+
+struct TmpfsInodeInner {
+    /// seconds since the epoch of the last read, change of the content and change of
+    /// the content or the links
+    atime: u64,
+    mtime: u64,
+    ctime: u64,
+    content: Content,
+}
+
+impl TmpfsInodeInner {
+    /// the content changed
+    fn touch(&mut self) {
+        let now = epoch_secs();
+        self.mtime = now;
+        self.ctime = now;
+    }
+}
+
+fn is_valid_name(name: &str) -> bool {
+    !name.is_empty() && name != "." && name != ".." && !name.contains('/')
+}
+
+impl TmpfsInode {
+    fn new(content: Content) -> Arc<Self> {
+        let now = epoch_secs();
+        Arc::new(Self {
+            ino: NEXT_INO.fetch_add(1, Ordering::Relaxed),
+            inner: unsafe {
+                UPSafeCell::new(TmpfsInodeInner {
+                    atime: now,
+                    mtime: now,
+                    ctime: now,
+                    content,
+                })
+            },
+        })
+    }
+
+    fn new_file() -> Arc<Self> {
+        Self::new(Content::File {
+            pages: BTreeMap::new(),
+            size: 0,
+        })
+    }
+
+    fn new_dir() -> Arc<Self> {
+        Self::new(Content::Dir(BTreeMap::new()))
+    }
+
+    /// an empty directory, the root of a new tmpfs
+    pub fn new_root() -> Arc<Self> {
+        Self::new_dir()
+    }
+
+    /// set every timestamp, for content coming from an archive
+    pub fn set_mtime(&self, mtime: u64) {
+        let mut inner = self.inner.exclusive_access();
+        inner.atime = mtime;
+        inner.mtime = mtime;
+        inner.ctime = mtime;
+    }
+
+    /// the entry `name` of this directory
+    pub fn get(&self, name: &str) -> Option<Arc<TmpfsInode>> {
+        match &self.inner.exclusive_access().content {
+            Content::Dir(entries) => entries.get(name).cloned(),
+            _ => None,
+        }
+    }
+
+    /// add `inode` as `name`, fail if this is not a directory or `name` is taken
+    fn add(&self, name: &str, inode: Arc<TmpfsInode>) -> Option<Arc<TmpfsInode>> {
+        if !is_valid_name(name) {
+            return None;
+        }
+        let mut inner = self.inner.exclusive_access();
+        match &mut inner.content {
+            Content::Dir(entries) if !entries.contains_key(name) => {
+                entries.insert(String::from(name), inode.clone());
+            }
+            _ => return None,
+        }
+        inner.touch();
+        Some(inode)
+    }
+
+    /// the subdirectory `name`, created if it does not exist yet
+    pub fn mkdir(&self, name: &str) -> Option<Arc<TmpfsInode>> {
+        match self.get(name) {
+            Some(dir) if dir.is_dir() => Some(dir),
+            Some(_) => None,
+            None => self.add(name, Self::new_dir()),
+        }
+    }
+
+    /// a new regular file `name` holding `data`
+    pub fn create_file(&self, name: &str, data: &[u8]) -> Option<Arc<TmpfsInode>> {
+        let file = Self::new_file();
+        if file.write_at(0, data) < data.len() {
+            return None;
+        }
+        self.add(name, file)
+    }
+
+    /// a new symbolic link `name` to `target`
+    pub fn symlink(&self, name: &str, target: &str) -> Option<Arc<TmpfsInode>> {
+        self.add(name, Self::new(Content::Symlink(String::from(target))))
+    }
+
+    fn is_dir(&self) -> bool {
+        matches!(self.inner.exclusive_access().content, Content::Dir(_))
+    }
+
+    /// a directory holding nothing, or anything but a directory
+    fn is_empty_dir_or_other(&self) -> bool {
+        match &self.inner.exclusive_access().content {
+            Content::Dir(entries) => entries.is_empty(),
+            _ => true,
+        }
+    }
+
+    /// number of subdirectories
+    fn subdir_count(&self) -> usize {
+        match &self.inner.exclusive_access().content {
+            Content::Dir(entries) => entries.values().filter(|inode| inode.is_dir()).count(),
+            _ => 0,
+        }
+    }
+
+    /// true if `self` is `inode` or one of its ancestors
+    fn contains(&self, inode: &TmpfsInode) -> bool {
+        if self.ino == inode.ino {
+            return true;
+        }
+        match &self.inner.exclusive_access().content {
+            Content::Dir(entries) => entries.values().any(|entry| entry.contains(inode)),
+            _ => false,
+        }
+    }
+
+    /// remove the entry `name` of this directory and return it
+    fn take(&self, name: &str) -> Option<Arc<TmpfsInode>> {
+        let mut inner = self.inner.exclusive_access();
+        let inode = match &mut inner.content {
+            Content::Dir(entries) => entries.remove(name)?,
+            _ => return None,
+        };
+        inner.touch();
+        Some(inode)
+    }
+}
+
+impl Inode for TmpfsInode {
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> usize {
+        let mut inner = self.inner.exclusive_access();
+        let (pages, size) = match &inner.content {
+            Content::File { pages, size } => (pages, *size),
+            _ => return 0,
+        };
+        if offset >= size {
+            return 0;
+        }
+        let len = min(buf.len(), size - offset);
+        let mut read = 0;
+        while read < len {
+            let pos = offset + read;
+            let chunk = min(PAGE_SIZE - pos % PAGE_SIZE, len - read);
+            match pages.get(&(pos / PAGE_SIZE)) {
+                Some(frame) => buf[read..read + chunk].copy_from_slice(
+                    &frame.ppn.get_bytes_array()[pos % PAGE_SIZE..pos % PAGE_SIZE + chunk],
+                ),
+                None => buf[read..read + chunk].fill(0),
+            }
+            read += chunk;
+        }
+        inner.atime = epoch_secs();
+        len
+    }
+
+    /// Frames are only allocated for the pages written, the write is short when memory
+    /// runs out.
+    fn write_at(&self, offset: usize, buf: &[u8]) -> usize {
+        let mut inner = self.inner.exclusive_access();
+        let (pages, size) = match &mut inner.content {
+            Content::File { pages, size } => (pages, size),
+            _ => return 0,
+        };
+        if offset.checked_add(buf.len()).is_none() {
+            return 0;
+        }
+        let mut written = 0;
+        while written < buf.len() {
+            let pos = offset + written;
+            let index = pos / PAGE_SIZE;
+            if !pages.contains_key(&index) {
+                match frame_alloc() {
+                    Some(frame) => pages.insert(index, frame),
+                    None => break,
+                };
+            }
+            let page = pages[&index].ppn.get_bytes_array_mut();
+            let len = min(PAGE_SIZE - pos % PAGE_SIZE, buf.len() - written);
+            page[pos % PAGE_SIZE..pos % PAGE_SIZE + len]
+                .copy_from_slice(&buf[written..written + len]);
+            written += len;
+        }
+        if written > 0 {
+            *size = max(*size, offset + written);
+            inner.touch();
+        }
+        written
+    }
+
+    fn stat(&self) -> Stat {
+        let subdirs = self.subdir_count();
+        let inner = self.inner.exclusive_access();
+        let (mode, size) = match &inner.content {
+            Content::File { size, .. } => (StatMode::FILE, *size),
+            Content::Dir(entries) => (StatMode::DIR, entries.len()),
+            Content::Symlink(target) => (StatMode::LNK, target.len()),
+        };
+        let mut stat = Stat::new(self.ino, mode, size as u64);
+        if mode == StatMode::DIR {
+            // "." and the entry in the parent, plus ".." of every subdirectory
+            stat.nlink = 2 + subdirs as u32;
+        }
+        stat.atime = inner.atime;
+        stat.mtime = inner.mtime;
+        stat.ctime = inner.ctime;
+        stat
+    }
+
+    fn lookup(&self, name: &str) -> Option<Arc<dyn Inode>> {
+        self.get(name).map(|inode| inode as Arc<dyn Inode>)
+    }
+
+    fn create(&self, name: &str, mode: StatMode) -> Option<Arc<dyn Inode>> {
+        let inode = match mode {
+            StatMode::FILE => Self::new_file(),
+            StatMode::DIR => Self::new_dir(),
+            _ => return None,
+        };
+        self.add(name, inode).map(|inode| inode as Arc<dyn Inode>)
+    }
+
+    fn list(&self) -> Vec<String> {
+        match &self.inner.exclusive_access().content {
+            Content::Dir(entries) => entries.keys().cloned().collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// The frames are given back.
+    fn truncate(&self) -> bool {
+        let mut inner = self.inner.exclusive_access();
+        match &mut inner.content {
+            Content::File { pages, size } => {
+                pages.clear();
+                *size = 0;
+            }
+            _ => return false,
+        }
+        inner.touch();
+        true
+    }
+
+    fn unlink(&self, name: &str) -> bool {
+        match self.get(name) {
+            Some(inode) if inode.is_empty_dir_or_other() => self.take(name).is_some(),
+            _ => false,
+        }
+    }
+
+    fn readlink(&self) -> Option<String> {
+        match &self.inner.exclusive_access().content {
+            Content::Symlink(target) => Some(target.clone()),
+            _ => None,
+        }
+    }
+
+    fn rename(&self, old_name: &str, new_dir: &Arc<dyn Inode>, new_name: &str) -> bool {
+        let new_dir = match new_dir.as_any().downcast_ref::<TmpfsInode>() {
+            Some(dir) if dir.is_dir() => dir,
+            _ => return false,
+        };
+        let inode = match self.get(old_name) {
+            Some(inode) => inode,
+            None => return false,
+        };
+        if !is_valid_name(new_name) || inode.contains(new_dir) {
+            // a directory can not be moved into itself
+            return false;
+        }
+        if let Some(target) = new_dir.get(new_name) {
+            if target.ino == inode.ino {
+                return true;
+            }
+            if target.is_dir() != inode.is_dir() || !target.is_empty_dir_or_other() {
+                return false;
+            }
+            new_dir.take(new_name);
+        }
+        self.take(old_name);
+        inode.inner.exclusive_access().ctime = epoch_secs();
+        new_dir.add(new_name, inode).is_some()
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
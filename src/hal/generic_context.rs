@@ -5,5 +5,6 @@ pub trait GenericContext<T: Sized> {
 
     fn goto_trap_return(kstack_ptr: usize) -> T;
 
-    fn switch(current_task_cx_ptr: *mut T, next_task_cx_ptr: *const T) -> !;
+    /// Save the current context and resume the next one, returns when switched back
+    fn switch(current_task_cx_ptr: *mut T, next_task_cx_ptr: *const T);
 }
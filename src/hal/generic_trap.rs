@@ -1,4 +1,12 @@
 pub trait GenericTrap<T: Sized> {
     fn init();
     fn task_init_cx(entry: usize, user_sp: usize, kernel_sp: usize) -> T;
+    fn set_kernel_sp(&mut self, kernel_sp: usize);
+    /// stack pointer of the user program
+    fn user_sp(&self) -> usize;
+    /// make the user program call `handler(signum)` with the stack pointer `sp`,
+    /// the handler returning to `restorer`
+    fn enter_signal_handler(&mut self, handler: usize, signum: usize, restorer: usize, sp: usize);
+    /// take back the registers and the pc saved in `saved`, the privileged state is kept
+    fn restore_user_state(&mut self, saved: &T);
 }
@@ -20,8 +20,13 @@ pub use generic_trap::*;
 
 pub type Arch = ArchRISCV;
 
-pub fn init() {
-    riscv::init();
+/// `dtb` is the physical address of the device tree handed over by the firmware
+pub fn init(dtb: usize) {
+    riscv::init(dtb);
+}
+
+pub fn shutdown(failure: bool) -> ! {
+    riscv::sbi::shutdown(failure)
 }
 
 pub fn enable_timer_interrupt() {
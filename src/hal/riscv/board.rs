@@ -1,6 +1,270 @@
-pub const CLOCK_FREQ: usize = 12500000;
-pub const MEMORY_END: usize = 0x8800_0000;
+//! Board configuration, discovered from the device tree passed by SBI
+//!
+//! The constants are the ones of the qemu virt machine, used when there is no device tree.
+use crate::misc::fdt::Fdt;
+use crate::println;
+use crate::sync::upsafecell::UPSafeCell;
+use alloc::string::String;
+use alloc::vec::Vec;
+use lazy_static::*;
 
-pub const MMIO: &[(usize, usize)] = &[
-    (0x0010_0000, 0x00_2000), // VIRT_TEST/RTC  in virt machine
-];
+pub const DEFAULT_CLOCK_FREQ: usize = 12500000;
+pub const DEFAULT_MEMORY_START: usize = 0x8000_0000;
+pub const DEFAULT_MEMORY_END: usize = 0x8800_0000;
+
+pub const DEFAULT_UART: MmioDevice = MmioDevice {
+    base: 0x1000_0000,
+    size: 0x1000,
+    irq: 10,
+};
+
+pub const DEFAULT_PLIC: MmioDevice = MmioDevice {
+    base: 0x0c00_0000,
+    size: 0x40_0000,
+    irq: 0,
+};
+
+/// goldfish real time clock, inside the VIRT_TEST range of the virt machine
+pub const DEFAULT_RTC: MmioDevice = MmioDevice {
+    base: 0x0010_1000,
+    size: 0x1000,
+    irq: 11,
+};
+
+/// VIRT_TEST/RTC in virt machine
+pub const VIRT_TEST: MmioDevice = MmioDevice {
+    base: 0x0010_0000,
+    size: 0x2000,
+    irq: 0,
+};
+
+/// number of interrupt sources of the virt machine, source 0 does not exist
+pub const PLIC_MAX_IRQ: usize = 128;
+
+/// part of the PLIC that is mapped, up to the context of hart 7
+pub const PLIC_MAPPED_SIZE: usize = 0x21_0000;
+
+pub const MAX_MEMORY_REGIONS: usize = 4;
+pub const MAX_VIRTIO_DEVICES: usize = 8;
+pub const MAX_BOOTARGS_LEN: usize = 256;
+
+#[derive(Copy, Clone, Debug)]
+pub struct MmioDevice {
+    pub base: usize,
+    pub size: usize,
+    /// interrupt source on the PLIC, 0 if there is none
+    pub irq: usize,
+}
+
+pub struct BoardInfo {
+    /// (start, end) of each memory region
+    pub memory: [(usize, usize); MAX_MEMORY_REGIONS],
+    pub memory_count: usize,
+    /// frequency of the `time` csr
+    pub clock_freq: usize,
+    pub hart_count: usize,
+    pub uart: MmioDevice,
+    pub plic: MmioDevice,
+    pub rtc: MmioDevice,
+    pub virtio: [Option<MmioDevice>; MAX_VIRTIO_DEVICES],
+    pub bootargs: [u8; MAX_BOOTARGS_LEN],
+    pub bootargs_len: usize,
+    /// (start, end) of the initramfs loaded by the boot loader
+    pub initrd: Option<(usize, usize)>,
+}
+
+impl BoardInfo {
+    fn new() -> Self {
+        Self {
+            memory: [(DEFAULT_MEMORY_START, DEFAULT_MEMORY_END); MAX_MEMORY_REGIONS],
+            memory_count: 1,
+            clock_freq: DEFAULT_CLOCK_FREQ,
+            hart_count: 1,
+            uart: DEFAULT_UART,
+            plic: DEFAULT_PLIC,
+            rtc: DEFAULT_RTC,
+            virtio: [None; MAX_VIRTIO_DEVICES],
+            bootargs: [0; MAX_BOOTARGS_LEN],
+            bootargs_len: 0,
+            initrd: None,
+        }
+    }
+
+    fn parse(&mut self, fdt: &Fdt) {
+        let mut memory_count = 0;
+        let mut hart_count = 0;
+        let mut virtio_count = 0;
+        fdt.for_each_node(|node| {
+            let irq = node
+                .property_u32("interrupts")
+                .map_or(0, |irq| irq as usize);
+            let device = node
+                .reg(0)
+                .map(|(base, size)| MmioDevice { base, size, irq });
+
+            if node.property_str("device_type") == Some("memory") {
+                let mut index = 0;
+                while let Some((start, size)) = node.reg(index) {
+                    if memory_count < MAX_MEMORY_REGIONS {
+                        self.memory[memory_count] = (start, start + size);
+                        memory_count += 1;
+                    }
+                    index += 1;
+                }
+            } else if node.base_name() == "cpus" {
+                if let Some(freq) = node.property_u32("timebase-frequency") {
+                    self.clock_freq = freq as usize;
+                }
+            } else if node.property_str("device_type") == Some("cpu") {
+                hart_count += 1;
+                if let Some(freq) = node.property_u32("timebase-frequency") {
+                    self.clock_freq = freq as usize;
+                }
+            } else if node.depth == 1 && node.base_name() == "chosen" {
+                if let Some(bootargs) = node.property_str("bootargs") {
+                    let len = core::cmp::min(bootargs.len(), MAX_BOOTARGS_LEN);
+                    self.bootargs[..len].copy_from_slice(&bootargs.as_bytes()[..len]);
+                    self.bootargs_len = len;
+                }
+                let start = node.property("linux,initrd-start").and_then(read_cell);
+                let end = node.property("linux,initrd-end").and_then(read_cell);
+                if let (Some(start), Some(end)) = (start, end) {
+                    if start < end {
+                        self.initrd = Some((start, end));
+                    }
+                }
+            } else if node.is_compatible("ns16550a") {
+                if let Some(device) = device {
+                    self.uart = device;
+                }
+            } else if node.is_compatible("riscv,plic0") || node.is_compatible("sifive,plic-1.0.0") {
+                if let Some(device) = device {
+                    self.plic = device;
+                }
+            } else if node.is_compatible("google,goldfish-rtc") {
+                if let Some(device) = device {
+                    self.rtc = device;
+                }
+            } else if node.is_compatible("virtio,mmio") {
+                if let Some(device) = device {
+                    if virtio_count < MAX_VIRTIO_DEVICES {
+                        self.virtio[virtio_count] = Some(device);
+                        virtio_count += 1;
+                    }
+                }
+            }
+        });
+        if memory_count > 0 {
+            self.memory_count = memory_count;
+        }
+        if hart_count > 0 {
+            self.hart_count = hart_count;
+        }
+    }
+}
+
+/// a property holding one address, in one or two cells
+fn read_cell(value: &[u8]) -> Option<usize> {
+    match value.len() {
+        4 => Some(u32::from_be_bytes(value.try_into().ok()?) as usize),
+        8 => Some(u64::from_be_bytes(value.try_into().ok()?) as usize),
+        _ => None,
+    }
+}
+
+lazy_static! {
+    pub static ref BOARD_INFO: UPSafeCell<BoardInfo> = unsafe { UPSafeCell::new(BoardInfo::new()) };
+}
+
+/// Parse the device tree at physical address `dtb`, keep the defaults if it is invalid.
+/// Everything is copied out, so the blob may be overwritten afterwards.
+pub fn init(dtb: usize) {
+    let mut info = BOARD_INFO.exclusive_access();
+    match unsafe { Fdt::from_ptr(dtb as *const u8) } {
+        Some(fdt) => info.parse(&fdt),
+        None => println!("[kernel] no valid device tree at {:#x}, use defaults", dtb),
+    }
+    println!(
+        "[kernel] harts: {}, timebase: {} Hz, memory: [{:#x}, {:#x})",
+        info.hart_count, info.clock_freq, info.memory[0].0, info.memory[0].1,
+    );
+    println!(
+        "[kernel] uart: {:#x}, plic: {:#x}, virtio devices: {}, bootargs: {:?}",
+        info.uart.base,
+        info.plic.base,
+        info.virtio.iter().flatten().count(),
+        core::str::from_utf8(&info.bootargs[..info.bootargs_len]).unwrap_or(""),
+    );
+}
+
+pub fn clock_freq() -> usize {
+    BOARD_INFO.exclusive_access().clock_freq
+}
+
+pub fn hart_count() -> usize {
+    BOARD_INFO.exclusive_access().hart_count
+}
+
+/// end of the memory region holding the kernel
+pub fn memory_end() -> usize {
+    extern "C" {
+        fn ekernel();
+    }
+    let info = BOARD_INFO.exclusive_access();
+    info.memory[..info.memory_count]
+        .iter()
+        .find(|(start, end)| *start <= ekernel as usize && (ekernel as usize) < *end)
+        .map_or(DEFAULT_MEMORY_END, |(_, end)| *end)
+}
+
+/// (start, end) of the initramfs passed in `/chosen`, if any
+pub fn initrd() -> Option<(usize, usize)> {
+    BOARD_INFO.exclusive_access().initrd
+}
+
+pub fn uart() -> MmioDevice {
+    BOARD_INFO.exclusive_access().uart
+}
+
+pub fn plic() -> MmioDevice {
+    BOARD_INFO.exclusive_access().plic
+}
+
+pub fn rtc() -> MmioDevice {
+    BOARD_INFO.exclusive_access().rtc
+}
+
+pub fn virtio_devices() -> Vec<MmioDevice> {
+    BOARD_INFO
+        .exclusive_access()
+        .virtio
+        .iter()
+        .flatten()
+        .copied()
+        .collect()
+}
+
+pub fn bootargs() -> String {
+    let info = BOARD_INFO.exclusive_access();
+    String::from(core::str::from_utf8(&info.bootargs[..info.bootargs_len]).unwrap_or(""))
+}
+
+/// (start, size) of every mmio range the kernel maps
+pub fn mmio_regions() -> Vec<(usize, usize)> {
+    let info = BOARD_INFO.exclusive_access();
+    let mut regions = Vec::new();
+    regions.push((VIRT_TEST.base, VIRT_TEST.size));
+    regions.push((
+        info.plic.base,
+        core::cmp::min(info.plic.size, PLIC_MAPPED_SIZE),
+    ));
+    regions.push((info.uart.base, info.uart.size));
+    let rtc = info.rtc;
+    if rtc.base < VIRT_TEST.base || rtc.base + rtc.size > VIRT_TEST.base + VIRT_TEST.size {
+        regions.push((rtc.base, rtc.size));
+    }
+    for device in info.virtio.iter().flatten() {
+        regions.push((device.base, device.size));
+    }
+    regions
+}
@@ -1,13 +1,21 @@
-//! SBI console driver, for text output
-use crate::hal::riscv::sbi::console_putchar;
+//! Console driver, for text input and output
+//!
+//! The uart is used once it is initialized, SBI serves as the early-boot fallback.
+pub mod uart;
+
+use crate::hal::riscv::sbi::{console_getchar, console_putchar};
 use core::fmt::{self, Write};
 
 struct Stdout;
 
 impl Write for Stdout {
     fn write_str(&mut self, s: &str) -> fmt::Result {
-        for c in s.chars() {
-            console_putchar(c as usize);
+        if uart::is_ready() {
+            s.bytes().for_each(uart::putchar);
+        } else {
+            for c in s.chars() {
+                console_putchar(c as usize);
+            }
         }
         Ok(())
     }
@@ -17,6 +25,29 @@ pub fn print(args: fmt::Arguments) {
     Stdout.write_fmt(args).unwrap();
 }
 
+/// Write raw bytes to the host console, they need not be UTF-8
+pub fn write_bytes(data: &[u8]) {
+    if uart::is_ready() {
+        data.iter().copied().for_each(uart::putchar);
+    } else {
+        for &c in data {
+            console_putchar(c as usize);
+        }
+    }
+}
+
+/// Get a character from the host console, None if there is no input yet
+pub fn getchar() -> Option<u8> {
+    if uart::is_ready() {
+        return uart::getchar();
+    }
+    // legacy sbi returns -1 when no character is available
+    match console_getchar() as isize {
+        -1 => None,
+        c => Some(c as u8),
+    }
+}
+
 /// Print! to the host console using the format string and arguments.
 #[macro_export]
 macro_rules! print {
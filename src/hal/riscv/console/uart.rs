@@ -0,0 +1,137 @@
+//! NS16550A uart driver of the qemu virt machine
+use crate::hal::riscv::board;
+use crate::misc::ring_buffer::RingBuffer;
+use crate::sync::upsafecell::UPSafeCell;
+use core::ptr::{read_volatile, write_volatile};
+use core::sync::atomic::{AtomicBool, Ordering};
+use lazy_static::*;
+
+/// receive buffer register, read only
+const RBR: usize = 0;
+/// transmit holding register, write only
+const THR: usize = 0;
+/// divisor latch low byte, when DLAB is set
+const DLL: usize = 0;
+/// interrupt enable register
+const IER: usize = 1;
+/// divisor latch high byte, when DLAB is set
+const DLM: usize = 1;
+/// fifo control register, write only
+const FCR: usize = 2;
+/// line control register
+const LCR: usize = 3;
+/// modem control register
+const MCR: usize = 4;
+/// line status register
+const LSR: usize = 5;
+
+const IER_RX_AVAILABLE: u8 = 1 << 0;
+const LCR_WORD_LEN_8: u8 = 0b11;
+const LCR_DLAB: u8 = 1 << 7;
+const FCR_ENABLE_AND_CLEAR: u8 = 0b111;
+/// DTR, RTS and OUT2, OUT2 gates the interrupt line
+const MCR_DTR_RTS_OUT2: u8 = 0b1011;
+const LSR_DATA_READY: u8 = 1 << 0;
+const LSR_THR_EMPTY: u8 = 1 << 5;
+
+const BUFFER_SIZE: usize = 256;
+
+struct Uart {
+    base: usize,
+    rx: RingBuffer<BUFFER_SIZE>,
+    tx: RingBuffer<BUFFER_SIZE>,
+}
+
+impl Uart {
+    const fn new(base: usize) -> Self {
+        Self {
+            base,
+            rx: RingBuffer::new(),
+            tx: RingBuffer::new(),
+        }
+    }
+
+    fn read_reg(&self, reg: usize) -> u8 {
+        unsafe { read_volatile((self.base + reg) as *const u8) }
+    }
+
+    fn write_reg(&self, reg: usize, value: u8) {
+        unsafe { write_volatile((self.base + reg) as *mut u8, value) }
+    }
+
+    /// 8N1, fifo enabled, interrupt on received data
+    fn init(&mut self) {
+        self.write_reg(IER, 0);
+        self.write_reg(LCR, LCR_DLAB);
+        // divisor 1 is the highest baud rate, qemu does not care anyway
+        self.write_reg(DLL, 1);
+        self.write_reg(DLM, 0);
+        self.write_reg(LCR, LCR_WORD_LEN_8);
+        self.write_reg(FCR, FCR_ENABLE_AND_CLEAR);
+        self.write_reg(MCR, MCR_DTR_RTS_OUT2);
+        self.write_reg(IER, IER_RX_AVAILABLE);
+    }
+
+    /// move received bytes from the hardware fifo to the rx buffer,
+    /// bytes are dropped when the buffer is full
+    fn drain_rx(&mut self) {
+        while self.read_reg(LSR) & LSR_DATA_READY != 0 {
+            let byte = self.read_reg(RBR);
+            self.rx.push(byte);
+        }
+    }
+
+    /// feed the transmitter from the tx buffer as long as it accepts bytes
+    fn flush_tx(&mut self) {
+        while self.read_reg(LSR) & LSR_THR_EMPTY != 0 {
+            match self.tx.pop() {
+                Some(byte) => self.write_reg(THR, byte),
+                None => break,
+            }
+        }
+    }
+
+    fn putchar(&mut self, byte: u8) {
+        while self.tx.is_full() {
+            self.flush_tx();
+        }
+        self.tx.push(byte);
+        self.flush_tx();
+    }
+
+    fn getchar(&mut self) -> Option<u8> {
+        self.drain_rx();
+        self.rx.pop()
+    }
+}
+
+lazy_static! {
+    static ref UART: UPSafeCell<Uart> = unsafe { UPSafeCell::new(Uart::new(board::uart().base)) };
+}
+
+/// whether the uart is initialized, the console falls back to sbi until then
+static UART_READY: AtomicBool = AtomicBool::new(false);
+
+pub fn init() {
+    UART.exclusive_access().init();
+    UART_READY.store(true, Ordering::Release);
+}
+
+pub fn is_ready() -> bool {
+    UART_READY.load(Ordering::Acquire)
+}
+
+pub fn putchar(byte: u8) {
+    UART.exclusive_access().putchar(byte);
+}
+
+pub fn getchar() -> Option<u8> {
+    UART.exclusive_access().getchar()
+}
+
+/// uart interrupt handler, drain the receive fifo and keep transmitting
+pub fn handle_irq() {
+    let mut uart = UART.exclusive_access();
+    uart.drain_rx();
+    uart.flush_tx();
+}
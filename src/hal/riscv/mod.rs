@@ -3,6 +3,8 @@ pub mod board;
 pub mod console;
 pub mod context;
 pub mod paging;
+pub mod plic;
+pub mod rtc;
 pub mod sbi;
 pub mod syscall;
 pub mod trap;
@@ -45,8 +47,12 @@ impl GenericArch for ArchRISCV {
     type TrapContext = TrapContextRV64;
 }
 
-pub fn init() {
+pub fn init(dtb: usize) {
+    board::init(dtb);
     trap::init();
+    console::uart::init();
+    plic::init();
+    plic::register_irq(board::uart().irq, console::uart::handle_irq);
 }
 
 pub fn activate_virt_mem(token: usize) {
@@ -0,0 +1,145 @@
+//! Platform-Level Interrupt Controller
+//!
+//! Each hart has a machine mode and a supervisor mode context, every context has its own
+//! enable bits, priority threshold and claim/complete register.
+use crate::hal::riscv::board::{self, PLIC_MAX_IRQ};
+use crate::sync::upsafecell::UPSafeCell;
+use core::ptr::{read_volatile, write_volatile};
+use lazy_static::*;
+
+const PRIORITY_OFFSET: usize = 0x0000;
+const ENABLE_OFFSET: usize = 0x2000;
+const ENABLE_STRIDE: usize = 0x80;
+const CONTEXT_OFFSET: usize = 0x20_0000;
+const CONTEXT_STRIDE: usize = 0x1000;
+const THRESHOLD: usize = 0x0;
+const CLAIM_COMPLETE: usize = 0x4;
+
+/// priority of sources registered through `register_irq`
+const DEFAULT_PRIORITY: u32 = 1;
+
+#[derive(Copy, Clone)]
+pub enum TargetPriority {
+    Machine = 0,
+    Supervisor = 1,
+}
+
+pub struct PLIC {
+    base: usize,
+}
+
+impl PLIC {
+    pub const fn new(base: usize) -> Self {
+        Self { base }
+    }
+
+    fn context(hart_id: usize, target: TargetPriority) -> usize {
+        hart_id * 2 + target as usize
+    }
+
+    fn priority_ptr(&self, irq: usize) -> *mut u32 {
+        assert!(irq > 0 && irq < PLIC_MAX_IRQ);
+        (self.base + PRIORITY_OFFSET + irq * 4) as *mut u32
+    }
+
+    /// return the enable word of `irq` and the bit inside it
+    fn enable_ptr(&self, hart_id: usize, target: TargetPriority, irq: usize) -> (*mut u32, u32) {
+        let context = Self::context(hart_id, target);
+        let ptr = self.base + ENABLE_OFFSET + context * ENABLE_STRIDE + (irq / 32) * 4;
+        (ptr as *mut u32, 1 << (irq % 32))
+    }
+
+    fn context_ptr(&self, hart_id: usize, target: TargetPriority, reg: usize) -> *mut u32 {
+        let context = Self::context(hart_id, target);
+        (self.base + CONTEXT_OFFSET + context * CONTEXT_STRIDE + reg) as *mut u32
+    }
+
+    /// priority 0 means never interrupt, 7 is the highest
+    pub fn set_priority(&self, irq: usize, priority: u32) {
+        assert!(priority < 8);
+        unsafe { write_volatile(self.priority_ptr(irq), priority) }
+    }
+
+    pub fn enable(&self, hart_id: usize, target: TargetPriority, irq: usize) {
+        let (ptr, bit) = self.enable_ptr(hart_id, target, irq);
+        unsafe { write_volatile(ptr, read_volatile(ptr) | bit) }
+    }
+
+    pub fn disable(&self, hart_id: usize, target: TargetPriority, irq: usize) {
+        let (ptr, bit) = self.enable_ptr(hart_id, target, irq);
+        unsafe { write_volatile(ptr, read_volatile(ptr) & !bit) }
+    }
+
+    /// only sources with a priority above the threshold interrupt the context
+    pub fn set_threshold(&self, hart_id: usize, target: TargetPriority, threshold: u32) {
+        assert!(threshold < 8);
+        unsafe { write_volatile(self.context_ptr(hart_id, target, THRESHOLD), threshold) }
+    }
+
+    /// claim the highest priority pending source, 0 if there is none
+    pub fn claim(&self, hart_id: usize, target: TargetPriority) -> usize {
+        unsafe { read_volatile(self.context_ptr(hart_id, target, CLAIM_COMPLETE)) as usize }
+    }
+
+    pub fn complete(&self, hart_id: usize, target: TargetPriority, irq: usize) {
+        unsafe {
+            write_volatile(
+                self.context_ptr(hart_id, target, CLAIM_COMPLETE),
+                irq as u32,
+            )
+        }
+    }
+}
+
+lazy_static! {
+    /// PLIC of the board, its registers are only touched through the methods above
+    pub static ref PLIC_DEVICE: PLIC = PLIC::new(board::plic().base);
+    /// handlers of external interrupt sources, indexed by irq number
+    static ref IRQ_HANDLERS: UPSafeCell<[Option<fn()>; PLIC_MAX_IRQ]> =
+        unsafe { UPSafeCell::new([None; PLIC_MAX_IRQ]) };
+}
+
+/// accept every registered source on the supervisor context of the boot hart
+pub fn init() {
+    PLIC_DEVICE.set_threshold(0, TargetPriority::Machine, 7);
+    PLIC_DEVICE.set_threshold(0, TargetPriority::Supervisor, 0);
+    unsafe {
+        riscv::register::sie::set_sext();
+    }
+}
+
+/// attach `handler` to `irq` and enable the source for the supervisor context
+pub fn register_irq(irq: usize, handler: fn()) {
+    IRQ_HANDLERS.exclusive_access()[irq] = Some(handler);
+    PLIC_DEVICE.set_priority(irq, DEFAULT_PRIORITY);
+    PLIC_DEVICE.enable(0, TargetPriority::Supervisor, irq);
+}
+
+/// detach the handler of `irq` and disable the source
+pub fn unregister_irq(irq: usize) {
+    PLIC_DEVICE.disable(0, TargetPriority::Supervisor, irq);
+    IRQ_HANDLERS.exclusive_access()[irq] = None;
+}
+
+/// claim, dispatch and complete a supervisor external interrupt
+pub fn handle_external_interrupt() {
+    let irq = PLIC_DEVICE.claim(0, TargetPriority::Supervisor);
+    if irq == 0 {
+        return;
+    }
+    let handler = IRQ_HANDLERS.exclusive_access()[irq];
+    match handler {
+        Some(handler) => handler(),
+        None => panic!("Unregistered external interrupt: irq {}", irq),
+    }
+    PLIC_DEVICE.complete(0, TargetPriority::Supervisor, irq);
+}
+
+/// Sleep until an interrupt is pending and dispatch it if it is an external one.
+/// For kernel code waiting on a device, as the kernel runs with interrupts disabled.
+pub fn wait_for_external_interrupt() {
+    unsafe {
+        riscv::asm::wfi();
+    }
+    handle_external_interrupt();
+}
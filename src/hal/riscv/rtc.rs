@@ -0,0 +1,22 @@
+//! Goldfish real time clock, the wall clock of the qemu virt machine
+use crate::hal::riscv::board;
+
+const TIME_LOW: usize = 0x00;
+const TIME_HIGH: usize = 0x04;
+const NSEC_PER_SEC: u64 = 1_000_000_000;
+
+/// nanoseconds since the epoch
+pub fn read_time_ns() -> u64 {
+    let base = board::rtc().base;
+    unsafe {
+        // reading the low half latches the high one
+        let low = ((base + TIME_LOW) as *const u32).read_volatile() as u64;
+        let high = ((base + TIME_HIGH) as *const u32).read_volatile() as u64;
+        (high << 32) | low
+    }
+}
+
+/// seconds since the epoch
+pub fn epoch_secs() -> u64 {
+    read_time_ns() / NSEC_PER_SEC
+}
@@ -1,29 +1,272 @@
-use crate::print;
+use crate::drivers::block::sync_all;
+use crate::fs::{
+    lookup, make_dir, make_pipe, open_file, rename_file, unlink_file, OpenFlags, SeekFrom, Stat,
+    StatMode,
+};
+use crate::mm::user::{copy_to_user, read_cstr, UserPtr, UserSlice};
+use crate::sysconfig::MAX_FDS;
 use crate::task::cpu::current_task;
 
-const FD_STDIN: usize = 0;
-const FD_STDOUT: usize = 1;
-
-pub fn sys_write(fd: usize, buf: *const u8, len: usize) -> isize {
-    match fd {
-        FD_STDOUT => {
-            let buffers = current_task()
-                .expect("No current task")
-                .inner_exclusive_access()
-                .memory_set
-                .translate_bytes_buffer(buf, len);
-            for buffer in buffers {
-                print!("{}", core::str::from_utf8(buffer).unwrap());
-            }
-            return len as isize;
+const SEEK_SET: usize = 0;
+const SEEK_CUR: usize = 1;
+const SEEK_END: usize = 2;
+
+/// too many open files
+const EMFILE: isize = -24;
+
+pub fn sys_write(fd: usize, buf: usize, len: usize) -> isize {
+    let task = current_task().expect("No current task.");
+    let mut inner = task.inner_exclusive_access();
+    let file = match inner.get_file(fd) {
+        Some(file) if file.writable() => file,
+        _ => return -1,
+    };
+    let buffer = match UserSlice::new(buf, len).readable(&mut inner.memory_set) {
+        Ok(buffer) => buffer,
+        Err(error) => return error.into(),
+    };
+    // release the task, writing may block
+    drop(inner);
+    match file.write(buffer) {
+        // the file can not take more: no space left, or a pipe without reader
+        0 if len > 0 => file.write_error(),
+        written => written as isize,
+    }
+}
+
+pub fn sys_read(fd: usize, buf: usize, len: usize) -> isize {
+    let task = current_task().expect("No current task.");
+    let mut inner = task.inner_exclusive_access();
+    let file = match inner.get_file(fd) {
+        Some(file) if file.readable() => file,
+        _ => return -1,
+    };
+    let buffer = match UserSlice::new(buf, len).writable(&mut inner.memory_set) {
+        Ok(buffer) => buffer,
+        Err(error) => return error.into(),
+    };
+    // release the task, reading may block
+    drop(inner);
+    match file.read(buffer) {
+        // the end of the file, or a blocking read given up for a signal
+        0 if len > 0 => file.read_error(),
+        read => read as isize,
+    }
+}
+
+pub fn sys_open(path: UserPtr<u8>, flags: u32) -> isize {
+    let task = current_task().expect("No current task.");
+    let mut inner = task.inner_exclusive_access();
+    let path = match read_cstr(&mut inner.memory_set, path) {
+        Ok(path) => path,
+        Err(error) => return error.into(),
+    };
+    let flags = match OpenFlags::from_bits(flags) {
+        Some(flags) => flags,
+        None => return -1,
+    };
+    // release the task, resolving the path may look into it through procfs
+    let cwd = inner.cwd.clone();
+    drop(inner);
+    if let Some(inode) = open_file(&cwd, path.as_str(), flags) {
+        let mut inner = task.inner_exclusive_access();
+        let fd = match inner.alloc_fd() {
+            Some(fd) => fd,
+            None => return EMFILE,
+        };
+        inner.fd_table[fd] = Some(inode);
+        fd as isize
+    } else {
+        -1
+    }
+}
+
+pub fn sys_unlink(path: UserPtr<u8>) -> isize {
+    let task = current_task().expect("No current task.");
+    let mut inner = task.inner_exclusive_access();
+    let path = match read_cstr(&mut inner.memory_set, path) {
+        Ok(path) => path,
+        Err(error) => return error.into(),
+    };
+    let cwd = inner.cwd.clone();
+    drop(inner);
+    if unlink_file(&cwd, path.as_str()) {
+        0
+    } else {
+        -1
+    }
+}
+
+pub fn sys_mkdir(path: UserPtr<u8>) -> isize {
+    let task = current_task().expect("No current task.");
+    let mut inner = task.inner_exclusive_access();
+    let path = match read_cstr(&mut inner.memory_set, path) {
+        Ok(path) => path,
+        Err(error) => return error.into(),
+    };
+    let cwd = inner.cwd.clone();
+    drop(inner);
+    if make_dir(&cwd, path.as_str()) {
+        0
+    } else {
+        -1
+    }
+}
+
+pub fn sys_rename(old_path: UserPtr<u8>, new_path: UserPtr<u8>) -> isize {
+    let task = current_task().expect("No current task.");
+    let mut inner = task.inner_exclusive_access();
+    let (old_path, new_path) = match (
+        read_cstr(&mut inner.memory_set, old_path),
+        read_cstr(&mut inner.memory_set, new_path),
+    ) {
+        (Ok(old_path), Ok(new_path)) => (old_path, new_path),
+        (Err(error), _) | (_, Err(error)) => return error.into(),
+    };
+    let cwd = inner.cwd.clone();
+    drop(inner);
+    if rename_file(&cwd, old_path.as_str(), new_path.as_str()) {
+        0
+    } else {
+        -1
+    }
+}
+
+pub fn sys_chdir(path: UserPtr<u8>) -> isize {
+    let task = current_task().expect("No current task.");
+    let mut inner = task.inner_exclusive_access();
+    let path = match read_cstr(&mut inner.memory_set, path) {
+        Ok(path) => path,
+        Err(error) => return error.into(),
+    };
+    let cwd = inner.cwd.clone();
+    drop(inner);
+    match lookup(&cwd, path.as_str(), true) {
+        Some((path, dir)) if dir.stat().mode == StatMode::DIR => {
+            task.inner_exclusive_access().cwd = path;
+            0
         }
-        _ => {
-            panic!("Unsupported fd in sys_write!");
+        _ => -1,
+    }
+}
+
+/// copy the current directory with a trailing nul into `buf`, return its length with the
+/// nul, or -1 if it does not fit in `len` bytes
+pub fn sys_getcwd(buf: usize, len: usize) -> isize {
+    let task = current_task().expect("No current task.");
+    let mut inner = task.inner_exclusive_access();
+    let mut cwd = inner.cwd.clone().into_bytes();
+    cwd.push(0);
+    if cwd.len() > len {
+        return -1;
+    }
+    match copy_to_user(&mut inner.memory_set, buf, &cwd) {
+        Ok(()) => cwd.len() as isize,
+        Err(error) => error.into(),
+    }
+}
+
+/// open a pipe, store the fd of its read end then the one of its write end in `pipe`
+pub fn sys_pipe(pipe: UserPtr<[usize; 2]>) -> isize {
+    let task = current_task().expect("No current task.");
+    let mut inner = task.inner_exclusive_access();
+    let (read_end, write_end) = make_pipe();
+    let read_fd = match inner.alloc_fd() {
+        Some(fd) => fd,
+        None => return EMFILE,
+    };
+    inner.fd_table[read_fd] = Some(read_end);
+    let write_fd = match inner.alloc_fd() {
+        Some(fd) => fd,
+        None => {
+            inner.fd_table[read_fd] = None;
+            return EMFILE;
         }
+    };
+    inner.fd_table[write_fd] = Some(write_end);
+    if let Err(error) = pipe.write(&mut inner.memory_set, &[read_fd, write_fd]) {
+        // nobody can know the fds, so the pipe is closed right away
+        inner.fd_table[read_fd] = None;
+        inner.fd_table[write_fd] = None;
+        return error.into();
     }
     0
 }
 
-pub fn sys_read(fd: usize, buf: *const u8, len: usize) -> isize {
+pub fn sys_close(fd: usize) -> isize {
+    let task = current_task().expect("No current task.");
+    let mut inner = task.inner_exclusive_access();
+    if fd >= inner.fd_table.len() || inner.fd_table[fd].is_none() {
+        return -1;
+    }
+    inner.fd_table[fd].take();
+    0
+}
+
+pub fn sys_lseek(fd: usize, offset: isize, whence: usize) -> isize {
+    let pos = match whence {
+        SEEK_SET if offset >= 0 => SeekFrom::Start(offset as usize),
+        SEEK_CUR => SeekFrom::Current(offset),
+        SEEK_END => SeekFrom::End(offset),
+        _ => return -1,
+    };
+    let task = current_task().expect("No current task.");
+    let file = match task.inner_exclusive_access().get_file(fd) {
+        Some(file) => file,
+        None => return -1,
+    };
+    file.seek(pos).map_or(-1, |offset| offset as isize)
+}
+
+pub fn sys_dup(fd: usize) -> isize {
+    let task = current_task().expect("No current task.");
+    let mut inner = task.inner_exclusive_access();
+    let file = match inner.get_file(fd) {
+        Some(file) => file,
+        None => return -1,
+    };
+    let new_fd = match inner.alloc_fd() {
+        Some(fd) => fd,
+        None => return EMFILE,
+    };
+    inner.fd_table[new_fd] = Some(file);
+    new_fd as isize
+}
+
+/// make `new_fd` refer to the file of `old_fd`, closing `new_fd` first if it is open
+pub fn sys_dup2(old_fd: usize, new_fd: usize) -> isize {
+    if new_fd >= MAX_FDS {
+        return -1;
+    }
+    let task = current_task().expect("No current task.");
+    let mut inner = task.inner_exclusive_access();
+    let file = match inner.get_file(old_fd) {
+        Some(file) => file,
+        None => return -1,
+    };
+    while inner.fd_table.len() <= new_fd {
+        inner.fd_table.push(None);
+    }
+    inner.fd_table[new_fd] = Some(file);
+    new_fd as isize
+}
+
+pub fn sys_fstat(fd: usize, stat_ptr: UserPtr<Stat>) -> isize {
+    let task = current_task().expect("No current task.");
+    let file = match task.inner_exclusive_access().get_file(fd) {
+        Some(file) => file,
+        None => return -1,
+    };
+    // a procfs file looks into the tasks to make its status
+    let stat = file.stat();
+    match stat_ptr.write(&mut task.inner_exclusive_access().memory_set, &stat) {
+        Ok(()) => 0,
+        Err(error) => error.into(),
+    }
+}
+
+/// write every dirty cached block back to its device
+pub fn sys_sync() -> isize {
+    sync_all();
     0
 }
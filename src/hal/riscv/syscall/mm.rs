@@ -0,0 +1,78 @@
+use crate::hal::MapPermission;
+use crate::task::cpu::current_task;
+
+const PROT_READ: usize = 1 << 0;
+const PROT_WRITE: usize = 1 << 1;
+const PROT_EXEC: usize = 1 << 2;
+
+/// user pages can not be mapped without any of `R W X`, and `W` implies `R` on riscv
+fn prot_to_permission(prot: usize) -> Option<MapPermission> {
+    if prot & !(PROT_READ | PROT_WRITE | PROT_EXEC) != 0 || prot == 0 {
+        return None;
+    }
+    let mut permission = MapPermission::U;
+    if prot & PROT_READ != 0 {
+        permission |= MapPermission::R;
+    }
+    if prot & PROT_WRITE != 0 {
+        permission |= MapPermission::R | MapPermission::W;
+    }
+    if prot & PROT_EXEC != 0 {
+        permission |= MapPermission::X;
+    }
+    Some(permission)
+}
+
+/// set the program break to `addr` and return the new break,
+/// `addr` 0 only queries the current break
+pub fn sys_brk(addr: usize) -> isize {
+    let task = current_task().expect("No current task.");
+    if addr == 0 {
+        return task.inner_exclusive_access().program_brk as isize;
+    }
+    task.change_program_brk(addr)
+        .map_or(-1, |program_brk| program_brk as isize)
+}
+
+pub fn sys_mmap(start: usize, len: usize, prot: usize) -> isize {
+    let permission = match prot_to_permission(prot) {
+        Some(permission) => permission,
+        None => return -1,
+    };
+    current_task()
+        .expect("No current task.")
+        .inner_exclusive_access()
+        .memory_set
+        .mmap(start, len, permission)
+        .map_or(-1, |start| start as isize)
+}
+
+pub fn sys_munmap(start: usize, len: usize) -> isize {
+    if current_task()
+        .expect("No current task.")
+        .inner_exclusive_access()
+        .memory_set
+        .munmap(start, len)
+    {
+        0
+    } else {
+        -1
+    }
+}
+
+pub fn sys_mprotect(start: usize, len: usize, prot: usize) -> isize {
+    let permission = match prot_to_permission(prot) {
+        Some(permission) => permission,
+        None => return -1,
+    };
+    if current_task()
+        .expect("No current task.")
+        .inner_exclusive_access()
+        .memory_set
+        .mprotect(start, len, permission)
+    {
+        0
+    } else {
+        -1
+    }
+}
@@ -1,26 +1,93 @@
 mod fs;
+mod mm;
 mod proc;
+mod signal;
 mod timer;
 
-use crate::hal::riscv::syscall::fs::{sys_read, sys_write};
+use crate::hal::riscv::syscall::fs::{
+    sys_chdir, sys_close, sys_dup, sys_dup2, sys_fstat, sys_getcwd, sys_lseek, sys_mkdir, sys_open,
+    sys_pipe, sys_read, sys_rename, sys_sync, sys_unlink, sys_write,
+};
 
-use self::proc::sys_exit;
+use crate::mm::user::UserPtr;
 
+use self::mm::{sys_brk, sys_mmap, sys_mprotect, sys_munmap};
+use self::proc::{sys_exec, sys_exit, sys_fork, sys_getpid, sys_waitpid, sys_yield};
+use self::signal::{sys_kill, sys_sigaction, sys_sigprocmask, sys_sigreturn};
+
+const SYSCALL_GETCWD: usize = 17;
+const SYSCALL_DUP: usize = 23;
+const SYSCALL_DUP2: usize = 24;
+const SYSCALL_MKDIR: usize = 34;
+const SYSCALL_UNLINK: usize = 35;
+const SYSCALL_RENAME: usize = 38;
+const SYSCALL_CHDIR: usize = 49;
+const SYSCALL_OPEN: usize = 56;
+const SYSCALL_CLOSE: usize = 57;
+const SYSCALL_PIPE: usize = 59;
+const SYSCALL_LSEEK: usize = 62;
 const SYSCALL_READ: usize = 63;
 const SYSCALL_WRITE: usize = 64;
+const SYSCALL_FSTAT: usize = 80;
+const SYSCALL_SYNC: usize = 81;
 const SYSCALL_EXIT: usize = 93;
+const SYSCALL_YIELD: usize = 124;
+const SYSCALL_KILL: usize = 129;
+const SYSCALL_SIGACTION: usize = 134;
+const SYSCALL_SIGPROCMASK: usize = 135;
+const SYSCALL_SIGRETURN: usize = 139;
+const SYSCALL_GETPID: usize = 172;
+const SYSCALL_BRK: usize = 214;
+const SYSCALL_MUNMAP: usize = 215;
+const SYSCALL_FORK: usize = 220;
+const SYSCALL_EXEC: usize = 221;
+const SYSCALL_MMAP: usize = 222;
+const SYSCALL_MPROTECT: usize = 226;
+const SYSCALL_WAITPID: usize = 260;
 
 pub fn syscall(syscall_id: usize, args: [usize; 3]) -> isize {
     match syscall_id {
-        SYSCALL_READ => sys_read(args[0], args[1] as *const u8, args[2]),
-        SYSCALL_WRITE => sys_write(args[0], args[1] as *const u8, args[2]),
+        SYSCALL_GETCWD => sys_getcwd(args[0], args[1]),
+        SYSCALL_DUP => sys_dup(args[0]),
+        SYSCALL_DUP2 => sys_dup2(args[0], args[1]),
+        SYSCALL_MKDIR => sys_mkdir(UserPtr::new(args[0])),
+        SYSCALL_UNLINK => sys_unlink(UserPtr::new(args[0])),
+        SYSCALL_RENAME => sys_rename(UserPtr::new(args[0]), UserPtr::new(args[1])),
+        SYSCALL_CHDIR => sys_chdir(UserPtr::new(args[0])),
+        SYSCALL_OPEN => sys_open(UserPtr::new(args[0]), args[1] as u32),
+        SYSCALL_CLOSE => sys_close(args[0]),
+        SYSCALL_PIPE => sys_pipe(UserPtr::new(args[0])),
+        SYSCALL_LSEEK => sys_lseek(args[0], args[1] as isize, args[2]),
+        SYSCALL_READ => sys_read(args[0], args[1], args[2]),
+        SYSCALL_WRITE => sys_write(args[0], args[1], args[2]),
+        SYSCALL_FSTAT => sys_fstat(args[0], UserPtr::new(args[1])),
+        SYSCALL_SYNC => sys_sync(),
         SYSCALL_EXIT => sys_exit(args[0] as i32),
-        _ => {
-            panic!("Unsupported syscall: ID = {}", syscall_id);
+        SYSCALL_YIELD => sys_yield(),
+        SYSCALL_KILL => sys_kill(args[0], args[1]),
+        SYSCALL_SIGACTION => sys_sigaction(args[0], UserPtr::new(args[1]), UserPtr::new(args[2])),
+        SYSCALL_SIGPROCMASK => {
+            sys_sigprocmask(args[0], UserPtr::new(args[1]), UserPtr::new(args[2]))
         }
+        SYSCALL_SIGRETURN => sys_sigreturn(),
+        SYSCALL_GETPID => sys_getpid(),
+        SYSCALL_BRK => sys_brk(args[0]),
+        SYSCALL_MUNMAP => sys_munmap(args[0], args[1]),
+        SYSCALL_FORK => sys_fork(),
+        SYSCALL_EXEC => sys_exec(UserPtr::new(args[0])),
+        SYSCALL_MMAP => sys_mmap(args[0], args[1], args[2]),
+        SYSCALL_MPROTECT => sys_mprotect(args[0], args[1], args[2]),
+        SYSCALL_WAITPID => sys_waitpid(args[0] as isize, UserPtr::new(args[1])),
+        // ENOSYS, a user program must not bring the kernel down
+        _ => -1,
     }
 }
 
 pub fn set_next_trigger() {
     timer::set_next_trigger();
 }
+
+/// milliseconds since boot
+pub fn get_time_ms() -> usize {
+    timer::get_time_ms()
+}
@@ -1,3 +1,79 @@
+use crate::fs::{open_file, OpenFlags};
+use crate::mm::user::{read_cstr, UserPtr};
+use crate::task::cpu::current_task;
+use crate::task::sche::{add_task, exit_current_and_run_next, suspend_current};
+
 pub fn sys_exit(exit_code: i32) -> ! {
-    panic!("Exit!");
+    exit_current_and_run_next(exit_code);
+    panic!("Unreachable in sys_exit!");
+}
+
+pub fn sys_yield() -> isize {
+    suspend_current();
+    0
+}
+
+pub fn sys_getpid() -> isize {
+    current_task().expect("No current task.").get_pid() as isize
+}
+
+pub fn sys_fork() -> isize {
+    let current_task = current_task().expect("No current task.");
+    let new_task = current_task.fork();
+    let new_pid = new_task.get_pid();
+    // child returns 0 from fork
+    new_task.inner_exclusive_access().get_trap_cx().regs.a0 = 0;
+    add_task(new_task);
+    new_pid as isize
+}
+
+pub fn sys_exec(path: UserPtr<u8>) -> isize {
+    let task = current_task().expect("No current task.");
+    let mut inner = task.inner_exclusive_access();
+    let path = match read_cstr(&mut inner.memory_set, path) {
+        Ok(path) => path,
+        Err(error) => return error.into(),
+    };
+    let cwd = inner.cwd.clone();
+    drop(inner);
+    let file = open_file(&cwd, path.as_str(), OpenFlags::RDONLY);
+    match file {
+        Some(file) if task.exec(file.read_all().as_slice()) => 0,
+        _ => -1,
+    }
+}
+
+/// If there is no child process with the given pid, return -1.
+/// If the child process exists but has not exited yet, return -2.
+/// Otherwise reap it and return its pid.
+pub fn sys_waitpid(pid: isize, exit_code_ptr: UserPtr<i32>) -> isize {
+    let task = current_task().expect("No current task.");
+    let mut inner = task.inner_exclusive_access();
+    if !inner
+        .childern
+        .iter()
+        .any(|child| pid == -1 || pid as usize == child.get_pid())
+    {
+        return -1;
+    }
+
+    let pair = inner.childern.iter().enumerate().find(|(_, child)| {
+        child.inner_exclusive_access().is_zombie() && (pid == -1 || pid as usize == child.get_pid())
+    });
+    if let Some((idx, exit_code)) =
+        pair.map(|(idx, child)| (idx, child.inner_exclusive_access().exit_code))
+    {
+        // the child is only reaped once its exit code is stored
+        if !exit_code_ptr.is_null() {
+            if let Err(error) = exit_code_ptr.write(&mut inner.memory_set, &exit_code) {
+                return error.into();
+            }
+        }
+        // the child may still be held a moment by a procfs or kill lookup, it is freed
+        // when that one is done
+        let child = inner.childern.remove(idx);
+        child.get_pid() as isize
+    } else {
+        -2
+    }
 }
@@ -0,0 +1,77 @@
+use crate::mm::user::UserPtr;
+use crate::task::cpu::{current_task, current_trap_cx};
+use crate::task::signal::{
+    change_signal_mask, force_signal, kill, set_signal_action, sigreturn, SignalAction, SignalFlags,
+};
+
+pub fn sys_kill(pid: usize, signum: usize) -> isize {
+    if kill(pid, signum) {
+        0
+    } else {
+        -1
+    }
+}
+
+/// set the action of `signum` to `*action` unless it is null, store the previous one in
+/// `*old_action` unless it is null
+pub fn sys_sigaction(
+    signum: usize,
+    action: UserPtr<SignalAction>,
+    old_action: UserPtr<SignalAction>,
+) -> isize {
+    let task = current_task().expect("No current task.");
+    let action = if action.is_null() {
+        None
+    } else {
+        match action.read(&mut task.inner_exclusive_access().memory_set) {
+            Ok(action) => Some(action),
+            Err(error) => return error.into(),
+        }
+    };
+    let old = match set_signal_action(signum, action) {
+        Some(old) => old,
+        None => return -1,
+    };
+    if old_action.is_null() {
+        return 0;
+    }
+    match old_action.write(&mut task.inner_exclusive_access().memory_set, &old) {
+        Ok(()) => 0,
+        Err(error) => error.into(),
+    }
+}
+
+/// change the signal mask with `*set` unless it is null, store the previous one in
+/// `*old_set` unless it is null
+pub fn sys_sigprocmask(how: usize, set: UserPtr<u32>, old_set: UserPtr<u32>) -> isize {
+    let task = current_task().expect("No current task.");
+    let set = if set.is_null() {
+        None
+    } else {
+        match set.read(&mut task.inner_exclusive_access().memory_set) {
+            Ok(bits) => Some(SignalFlags::from_bits_truncate(bits)),
+            Err(error) => return error.into(),
+        }
+    };
+    let old = match change_signal_mask(how, set) {
+        Some(old) => old,
+        None => return -1,
+    };
+    if old_set.is_null() {
+        return 0;
+    }
+    match old_set.write(&mut task.inner_exclusive_access().memory_set, &old.bits()) {
+        Ok(()) => 0,
+        Err(error) => error.into(),
+    }
+}
+
+/// return from a signal handler, the task is killed if its stack holds no frame
+pub fn sys_sigreturn() -> isize {
+    if !sigreturn() {
+        force_signal(SignalFlags::SIGSEGV);
+        return -1;
+    }
+    // a0 of the resumed context, as the return value is written to it
+    current_trap_cx().regs.a0 as isize
+}
@@ -1,4 +1,4 @@
-use crate::hal::board::CLOCK_FREQ;
+use crate::hal::board::clock_freq;
 use crate::hal::sbi::set_timer;
 use riscv::register::time;
 
@@ -10,11 +10,11 @@ pub fn get_time() -> usize {
     time::read()
 }
 
-/// get current time in microseconds
+/// get current time in milliseconds
 pub fn get_time_ms() -> usize {
-    time::read() / (CLOCK_FREQ / MSEC_PER_SEC)
+    time::read() / (clock_freq() / MSEC_PER_SEC)
 }
 /// set the next timer interrupt
 pub fn set_next_trigger() {
-    set_timer(get_time() + CLOCK_FREQ / TICKS_PER_SEC);
+    set_timer(get_time() + clock_freq() / TICKS_PER_SEC);
 }
@@ -6,6 +6,7 @@ use crate::println;
 use crate::task::cpu;
 use crate::task::cpu::current_task_token_ppn;
 use crate::task::sche::suspend_current;
+use crate::task::signal::{force_signal, handle_signals, SignalFlags};
 use crate::{hal::generic_trap::GenericTrap, sysconfig::TRAMPOLINE, sysconfig::TRAP_CONTEXT_BASE};
 use core::arch::global_asm;
 use riscv::register::{
@@ -65,19 +66,46 @@ pub extern "C" fn trap_handler() {
     match scause.cause() {
         Trap::Exception(Exception::UserEnvCall) => {
             // jump to next instruction anyway
-            let cx: &mut TrapContext = PhysAddr::from(
-                cpu::current_task()
-                    .expect("No current task.")
-                    .inner_exclusive_access()
-                    .trap_cx_ppn,
-            )
-            .get_mut();
+            let mut cx = cpu::current_trap_cx();
             cx.sepc += 4;
             // get system call return value
             let result = syscall(cx.regs.a7, [cx.regs.a0, cx.regs.a1, cx.regs.a2]);
             // cx is changed during sys_exec, so we have to call it again
+            cx = cpu::current_trap_cx();
             cx.regs.a0 = result as usize;
         }
+        Trap::Exception(Exception::InstructionPageFault)
+        | Trap::Exception(Exception::LoadPageFault)
+        | Trap::Exception(Exception::StorePageFault) => {
+            let vpn = VirtAddr::from(stval).pagenum_floor();
+            let is_write = matches!(scause.cause(), Trap::Exception(Exception::StorePageFault));
+            if !cpu::current_task()
+                .expect("No current task.")
+                .inner_exclusive_access()
+                .memory_set
+                .handle_page_fault(vpn, is_write)
+            {
+                user_fault(SignalFlags::SIGSEGV, scause.cause(), stval);
+            }
+        }
+        Trap::Exception(Exception::IllegalInstruction) => {
+            user_fault(SignalFlags::SIGILL, scause.cause(), stval);
+        }
+        Trap::Exception(Exception::Breakpoint) => {
+            user_fault(SignalFlags::SIGTRAP, scause.cause(), stval);
+        }
+        Trap::Exception(Exception::InstructionMisaligned)
+        | Trap::Exception(Exception::LoadMisaligned)
+        | Trap::Exception(Exception::StoreMisaligned) => {
+            user_fault(SignalFlags::SIGBUS, scause.cause(), stval);
+        }
+        // access faults, instruction page faults and anything unknown
+        Trap::Exception(_) => {
+            user_fault(SignalFlags::SIGSEGV, scause.cause(), stval);
+        }
+        Trap::Interrupt(Interrupt::SupervisorExternal) => {
+            crate::hal::riscv::plic::handle_external_interrupt();
+        }
         Trap::Interrupt(Interrupt::SupervisorTimer) => {
             println!("\n!\n");
             crate::hal::syscall::set_next_trigger();
@@ -94,6 +122,19 @@ pub extern "C" fn trap_handler() {
     trap_return()
 }
 
+/// An exception caused by the user program: send it `signal`. Unless it has a handler for
+/// the signal, the task is terminated on its way back to user mode, so say why.
+fn user_fault(signal: SignalFlags, cause: Trap, stval: usize) {
+    let sepc = cpu::current_trap_cx().sepc;
+    let pid = cpu::current_task().expect("No current task.").get_pid();
+    if !force_signal(signal) {
+        println!(
+            "[kernel] pid {} killed by {:?}: scause: {:?}, stval: {:#x}, sepc: {:#x}",
+            pid, signal, cause, stval, sepc
+        );
+    }
+}
+
 #[no_mangle]
 #[link_section = ".text.trapk"]
 pub extern "C" fn trap_from_kernel() -> ! {
@@ -142,11 +183,33 @@ impl GenericTrap<TrapContextRV64> for TrapContextRV64 {
         cx
     }
 
+    fn set_kernel_sp(&mut self, kernel_sp: usize) {
+        self.kernel_sp = kernel_sp;
+    }
+
+    fn user_sp(&self) -> usize {
+        self.regs.sp
+    }
+
+    fn enter_signal_handler(&mut self, handler: usize, signum: usize, restorer: usize, sp: usize) {
+        self.regs.a0 = signum;
+        self.regs.ra = restorer;
+        self.regs.sp = sp;
+        self.sepc = handler;
+    }
+
+    fn restore_user_state(&mut self, saved: &TrapContextRV64) {
+        self.regs = saved.regs.clone();
+        self.sepc = saved.sepc;
+    }
+
     fn init() {}
 }
 
 #[no_mangle]
 pub extern "C" fn trap_return() -> ! {
+    // may enter a signal handler, or terminate the task and not return
+    handle_signals();
     set_trap_entry_user();
     extern "C" {
         fn __trapin();
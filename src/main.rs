@@ -3,11 +3,12 @@
 
 extern crate alloc;
 
+mod drivers;
+mod fs;
 mod hal;
 mod lang_items;
 mod misc;
 mod mm;
-mod ramfs;
 mod sync;
 mod sysconfig;
 mod task;
@@ -66,16 +67,18 @@ fn clear_bss() {
     }
 }
 
-fn kernel_init() {
+fn kernel_init(dtb: usize) {
     seg_info();
     clear_bss();
-    hal::init();
+    hal::init(dtb);
     mm::init();
+    drivers::init();
+    fs::init();
 }
 
 #[no_mangle]
-pub extern "C" fn rust_main() -> ! {
-    kernel_init();
+pub extern "C" fn rust_main(_hart_id: usize, dtb: usize) -> ! {
+    kernel_init(dtb);
     bootup_logo();
     task::init();
     task::sche::run_task();
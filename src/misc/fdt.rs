@@ -0,0 +1,204 @@
+//! Minimal flattened device tree (DTB) parser
+//!
+//! Only walks the structure block, which is all the kernel needs to discover the board.
+//! See the devicetree specification, chapter 5, for the format.
+
+const FDT_MAGIC: u32 = 0xd00d_feed;
+const FDT_BEGIN_NODE: u32 = 0x1;
+const FDT_END_NODE: u32 = 0x2;
+const FDT_PROP: u32 = 0x3;
+const FDT_NOP: u32 = 0x4;
+const FDT_END: u32 = 0x9;
+
+/// deepest node the walker keeps `#address-cells`/`#size-cells` for
+const MAX_DEPTH: usize = 16;
+
+fn read_be_u32(data: &[u8], offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..offset + 4)?;
+    Some(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+fn align4(offset: usize) -> usize {
+    (offset + 3) & !3
+}
+
+/// read a value made of `cells` big-endian u32 cells
+fn read_cells(data: &[u8], cells: usize) -> Option<usize> {
+    let mut value = 0;
+    for i in 0..cells {
+        value = (value << 32) | read_be_u32(data, i * 4)? as usize;
+    }
+    Some(value)
+}
+
+/// null terminated string starting at `offset`
+fn read_str(data: &[u8], offset: usize) -> Option<&str> {
+    let bytes = data.get(offset..)?;
+    let len = bytes.iter().position(|&b| b == 0)?;
+    core::str::from_utf8(&bytes[..len]).ok()
+}
+
+pub struct Fdt<'a> {
+    structs: &'a [u8],
+    strings: &'a [u8],
+}
+
+impl<'a> Fdt<'a> {
+    /// # Safety
+    /// `ptr` must point to a readable device tree blob, which lives as long as `'a`
+    pub unsafe fn from_ptr(ptr: *const u8) -> Option<Fdt<'a>> {
+        if ptr.is_null() || ptr as usize % 4 != 0 {
+            return None;
+        }
+        let header = core::slice::from_raw_parts(ptr, 8);
+        if read_be_u32(header, 0)? != FDT_MAGIC {
+            return None;
+        }
+        let total_size = read_be_u32(header, 4)? as usize;
+        Self::from_bytes(core::slice::from_raw_parts(ptr, total_size))
+    }
+
+    pub fn from_bytes(data: &'a [u8]) -> Option<Fdt<'a>> {
+        if read_be_u32(data, 0)? != FDT_MAGIC {
+            return None;
+        }
+        let off_dt_struct = read_be_u32(data, 8)? as usize;
+        let off_dt_strings = read_be_u32(data, 12)? as usize;
+        let size_dt_strings = read_be_u32(data, 32)? as usize;
+        let size_dt_struct = read_be_u32(data, 36)? as usize;
+        Some(Self {
+            structs: data.get(off_dt_struct..off_dt_struct + size_dt_struct)?,
+            strings: data.get(off_dt_strings..off_dt_strings + size_dt_strings)?,
+        })
+    }
+
+    /// visit every node in depth first order, stop at the first malformed token
+    pub fn for_each_node(&self, mut f: impl FnMut(&FdtNode<'a, '_>)) {
+        // (#address-cells, #size-cells) that children of the node at each depth use
+        let mut cells = [(2, 1); MAX_DEPTH + 1];
+        let mut depth = 0;
+        let mut offset = 0;
+        while let Some(token) = read_be_u32(self.structs, offset) {
+            offset += 4;
+            match token {
+                FDT_BEGIN_NODE => {
+                    let name = match read_str(self.structs, offset) {
+                        Some(name) => name,
+                        None => return,
+                    };
+                    offset = align4(offset + name.len() + 1);
+                    if depth >= MAX_DEPTH {
+                        return;
+                    }
+                    let parent_cells = if depth == 0 {
+                        cells[0]
+                    } else {
+                        cells[depth - 1]
+                    };
+                    let node = FdtNode {
+                        name,
+                        depth,
+                        address_cells: parent_cells.0,
+                        size_cells: parent_cells.1,
+                        props_offset: offset,
+                        fdt: self,
+                    };
+                    cells[depth] = (
+                        node.property_u32("#address-cells")
+                            .map_or(2, |cells| cells as usize),
+                        node.property_u32("#size-cells")
+                            .map_or(1, |cells| cells as usize),
+                    );
+                    f(&node);
+                    depth += 1;
+                }
+                FDT_END_NODE => {
+                    if depth == 0 {
+                        return;
+                    }
+                    depth -= 1;
+                }
+                FDT_PROP => {
+                    let len = match read_be_u32(self.structs, offset) {
+                        Some(len) => len as usize,
+                        None => return,
+                    };
+                    offset = align4(offset + 8 + len);
+                }
+                FDT_NOP => {}
+                FDT_END => return,
+                _ => return,
+            }
+        }
+    }
+}
+
+pub struct FdtNode<'a, 'b> {
+    /// node name with unit address, e.g. `memory@80000000`, empty for the root
+    pub name: &'a str,
+    /// 0 for the root node
+    pub depth: usize,
+    /// `#address-cells` of the parent, used by `reg`
+    pub address_cells: usize,
+    /// `#size-cells` of the parent, used by `reg`
+    pub size_cells: usize,
+    props_offset: usize,
+    fdt: &'b Fdt<'a>,
+}
+
+impl<'a, 'b> FdtNode<'a, 'b> {
+    /// node name without the unit address
+    pub fn base_name(&self) -> &'a str {
+        self.name.split('@').next().unwrap_or("")
+    }
+
+    /// raw value of a property of this node
+    pub fn property(&self, name: &str) -> Option<&'a [u8]> {
+        let structs = self.fdt.structs;
+        let mut offset = self.props_offset;
+        loop {
+            match read_be_u32(structs, offset)? {
+                FDT_PROP => {
+                    let len = read_be_u32(structs, offset + 4)? as usize;
+                    let name_offset = read_be_u32(structs, offset + 8)? as usize;
+                    let value = structs.get(offset + 12..offset + 12 + len)?;
+                    if read_str(self.fdt.strings, name_offset)? == name {
+                        return Some(value);
+                    }
+                    offset = align4(offset + 12 + len);
+                }
+                FDT_NOP => offset += 4,
+                // properties always come before child nodes
+                _ => return None,
+            }
+        }
+    }
+
+    pub fn property_u32(&self, name: &str) -> Option<u32> {
+        read_be_u32(self.property(name)?, 0)
+    }
+
+    /// string property, the first one of a string list
+    pub fn property_str(&self, name: &str) -> Option<&'a str> {
+        read_str(self.property(name)?, 0)
+    }
+
+    /// whether `compatible` lists `name`
+    pub fn is_compatible(&self, name: &str) -> bool {
+        self.property("compatible").map_or(false, |value| {
+            value
+                .split(|&b| b == 0)
+                .any(|compatible| compatible == name.as_bytes())
+        })
+    }
+
+    /// the `index`th (address, size) pair of `reg`
+    pub fn reg(&self, index: usize) -> Option<(usize, usize)> {
+        let value = self.property("reg")?;
+        let entry_size = (self.address_cells + self.size_cells) * 4;
+        let entry = value.get(index * entry_size..(index + 1) * entry_size)?;
+        let address = read_cells(entry, self.address_cells)?;
+        let size = read_cells(&entry[self.address_cells * 4..], self.size_cells)?;
+        Some((address, size))
+    }
+}
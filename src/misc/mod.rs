@@ -1,4 +1,6 @@
 pub mod bitmanip;
+pub mod fdt;
 pub mod linked_list;
 pub mod logger;
 pub mod range;
+pub mod ring_buffer;
@@ -14,3 +14,9 @@ pub fn init() {
             .init(KERNEL_HEAP.as_ptr() as usize, KERNEL_HEAP_SIZE);
     }
 }
+
+/// (total, allocated) bytes of the kernel heap
+pub fn heap_stats() -> (usize, usize) {
+    let heap = HEAP_ALLOCATOR.lock();
+    (heap.stats_total_bytes(), heap.stats_alloc_actual())
+}
@@ -1,2 +1,2 @@
 pub mod heap_allocator;
-pub use heap_allocator::init;
+pub use heap_allocator::{heap_stats, init};
@@ -8,11 +8,15 @@ use crate::mm::page_table::frame::FrameTracker;
 use crate::mm::page_table::PageTable;
 use crate::println;
 use crate::sync::upsafecell::UPSafeCell;
-use crate::sysconfig::{MEMORY_END, PAGE_SIZE, TRAMPOLINE, TRAP_CONTEXT_BASE, USER_STACK_SIZE};
+use crate::sysconfig::{
+    MMAP_BASE, MMAP_TOP, PAGE_SIZE, TRAMPOLINE, TRAP_CONTEXT_BASE, USER_SPACE_TOP, USER_STACK_SIZE,
+    USER_STACK_TOP,
+};
 use alloc::collections::BTreeMap;
 use alloc::sync::Arc;
 use alloc::vec::Vec;
 use core::cmp;
+use core::mem::{align_of, size_of};
 use lazy_static::*;
 
 type VPNRange = SimpleRange<VirtPageNum>;
@@ -36,9 +40,12 @@ extern "C" {
     fn strampoline();
 }
 
+#[derive(Copy, Clone, PartialEq, Debug)]
 pub enum MapType {
     Identical,
     Framed,
+    /// framed, but each page is backed by a zeroed frame on its first page fault
+    Lazy,
 }
 
 pub struct MemorySet {
@@ -77,6 +84,199 @@ impl MemorySet {
         self.segments.push(seg);
     }
 
+    /// remove the segment starting at `start_vpn` and unmap all of its pages
+    pub fn remove_segment_with_start_vpn(&mut self, start_vpn: VirtPageNum) {
+        if let Some((idx, seg)) = self
+            .segments
+            .iter_mut()
+            .enumerate()
+            .find(|(_, seg)| seg.vpn_range.get_start() == start_vpn)
+        {
+            seg.unmap(&mut self.page_table);
+            self.segments.remove(idx);
+        }
+    }
+
+    /// split the segment containing `vpn` in two, so that `vpn` becomes a segment boundary
+    fn split_segment_at(&mut self, vpn: VirtPageNum) {
+        if let Some(idx) = self
+            .segments
+            .iter()
+            .position(|seg| seg.vpn_range.get_start() < vpn && vpn < seg.vpn_range.get_end())
+        {
+            let right = self.segments[idx].split_off(vpn);
+            self.segments.insert(idx + 1, right);
+        }
+    }
+
+    /// unmap every page in [start_vpn, end_vpn), segments crossing the bounds are split
+    pub fn remove_range(&mut self, start_vpn: VirtPageNum, end_vpn: VirtPageNum) {
+        self.split_segment_at(start_vpn);
+        self.split_segment_at(end_vpn);
+        let mut idx = 0;
+        while idx < self.segments.len() {
+            let seg = &mut self.segments[idx];
+            if start_vpn <= seg.vpn_range.get_start() && seg.vpn_range.get_end() <= end_vpn {
+                seg.unmap(&mut self.page_table);
+                self.segments.remove(idx);
+            } else {
+                idx += 1;
+            }
+        }
+    }
+
+    /// grow the segment starting at `start` so that it ends at `new_end`,
+    /// return false if the new pages are already in use or frames run out
+    pub fn append_to(&mut self, start: VirtAddr, new_end: VirtAddr) -> bool {
+        let start_vpn = start.pagenum_floor();
+        let new_end_vpn = new_end.pagenum_ceil();
+        let old_end_vpn = match self
+            .segments
+            .iter()
+            .find(|seg| seg.vpn_range.get_start() == start_vpn)
+        {
+            Some(seg) => seg.vpn_range.get_end(),
+            None => return false,
+        };
+        if new_end_vpn <= old_end_vpn {
+            return true;
+        }
+        if !self.is_free_area(old_end_vpn, new_end_vpn) {
+            return false;
+        }
+        let page_table = &mut self.page_table;
+        self.segments
+            .iter_mut()
+            .find(|seg| seg.vpn_range.get_start() == start_vpn)
+            .map_or(false, |seg| seg.append_to(new_end_vpn, page_table))
+    }
+
+    /// shrink the segment starting at `start` so that it ends at `new_end`
+    pub fn shrink_to(&mut self, start: VirtAddr, new_end: VirtAddr) -> bool {
+        let start_vpn = start.pagenum_floor();
+        let new_end_vpn = new_end.pagenum_ceil();
+        let page_table = &mut self.page_table;
+        self.segments
+            .iter_mut()
+            .find(|seg| seg.vpn_range.get_start() == start_vpn)
+            .map_or(false, |seg| seg.shrink_to(new_end_vpn, page_table))
+    }
+
+    /// whether no segment overlaps [start_vpn, end_vpn)
+    fn is_free_area(&self, start_vpn: VirtPageNum, end_vpn: VirtPageNum) -> bool {
+        self.segments
+            .iter()
+            .all(|seg| seg.vpn_range.get_end() <= start_vpn || end_vpn <= seg.vpn_range.get_start())
+    }
+
+    /// first fit search of `page_count` free pages in the mmap area
+    fn find_free_area(&self, page_count: usize) -> Option<VirtPageNum> {
+        let mmap_top: VirtPageNum = VirtAddr::from(MMAP_TOP).pagenum_floor();
+        let mut ranges: Vec<(VirtPageNum, VirtPageNum)> = self
+            .segments
+            .iter()
+            .map(|seg| (seg.vpn_range.get_start(), seg.vpn_range.get_end()))
+            .collect();
+        ranges.sort();
+
+        let mut candidate: VirtPageNum = VirtAddr::from(MMAP_BASE).pagenum_floor();
+        for (start, end) in ranges {
+            let candidate_end = VirtPageNum::from(usize::from(candidate) + page_count);
+            if candidate_end <= start {
+                break;
+            }
+            candidate = cmp::max(candidate, end);
+        }
+        if usize::from(candidate) + page_count <= usize::from(mmap_top) {
+            Some(candidate)
+        } else {
+            None
+        }
+    }
+
+    /// pages of [start, start + len) if it is a page aligned, non empty range within
+    /// [base, top), the values coming from user space
+    fn user_page_range(
+        start: usize,
+        len: usize,
+        base: usize,
+        top: usize,
+    ) -> Option<(VirtPageNum, VirtPageNum)> {
+        if start % PAGE_SIZE != 0 || len == 0 || start < base {
+            return None;
+        }
+        let end = start.checked_add(len).filter(|end| *end <= top)?;
+        Some((
+            VirtAddr::from(start).pagenum_floor(),
+            VirtAddr::from(end).pagenum_ceil(),
+        ))
+    }
+
+    /// map `len` bytes of anonymous memory at `start`, or anywhere in the mmap area if `start` is 0.
+    /// return the start address of the new area
+    pub fn mmap(&mut self, start: usize, len: usize, permission: MapPermission) -> Option<usize> {
+        if start % PAGE_SIZE != 0 || len == 0 || len > MMAP_TOP {
+            return None;
+        }
+        let page_count = (len + PAGE_SIZE - 1) / PAGE_SIZE;
+        let start_vpn = if start == 0 {
+            self.find_free_area(page_count)?
+        } else {
+            // a fixed mapping stays in the mmap area, away from the program and its heap
+            let (start_vpn, end_vpn) =
+                Self::user_page_range(start, page_count * PAGE_SIZE, MMAP_BASE, MMAP_TOP)?;
+            if !self.is_free_area(start_vpn, end_vpn) {
+                return None;
+            }
+            start_vpn
+        };
+        let end_vpn = VirtPageNum::from(usize::from(start_vpn) + page_count);
+        self.insert_segment(
+            MapSegment::new(start_vpn.into(), end_vpn.into(), MapType::Lazy, permission),
+            None,
+        );
+        Some(VirtAddr::from(start_vpn).into())
+    }
+
+    /// unmap [start, start + len) in the mmap area, pages in the range which are not mapped
+    /// are ignored
+    pub fn munmap(&mut self, start: usize, len: usize) -> bool {
+        match Self::user_page_range(start, len, MMAP_BASE, MMAP_TOP) {
+            Some((start_vpn, end_vpn)) => {
+                self.remove_range(start_vpn, end_vpn);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// change the permission of [start, start + len), every page in the range must be mapped
+    pub fn mprotect(&mut self, start: usize, len: usize, permission: MapPermission) -> bool {
+        let (start_vpn, end_vpn) = match Self::user_page_range(start, len, 0, MMAP_TOP) {
+            Some(range) => range,
+            None => return false,
+        };
+        let covered: usize = self
+            .segments
+            .iter()
+            .map(|seg| seg.overlap_count(start_vpn, end_vpn))
+            .sum();
+        if covered != usize::from(end_vpn) - usize::from(start_vpn) {
+            return false;
+        }
+
+        self.split_segment_at(start_vpn);
+        self.split_segment_at(end_vpn);
+        let page_table = &mut self.page_table;
+        self.segments
+            .iter_mut()
+            .filter(|seg| {
+                start_vpn <= seg.vpn_range.get_start() && seg.vpn_range.get_end() <= end_vpn
+            })
+            .for_each(|seg| seg.set_permission(permission, page_table));
+        true
+    }
+
     fn map_trampoline(&mut self) {
         self.page_table.map(
             VirtAddr::from(TRAMPOLINE).into(),
@@ -137,73 +337,124 @@ impl MemorySet {
         memory_set.insert_segment(
             MapSegment::new(
                 (ekernel as usize).into(),
-                MEMORY_END.into(),
+                board::memory_end().into(),
                 MapType::Identical,
                 MapPermission::R | MapPermission::W,
             ),
             None,
         );
+        for (start, len) in board::mmio_regions() {
+            memory_set.insert_segment(
+                MapSegment::new(
+                    start.into(),
+                    (start + len).into(),
+                    MapType::Identical,
+                    MapPermission::R | MapPermission::W,
+                ),
+                None,
+            );
+        }
 
         memory_set
     }
 
-    /// return (MemorySet, uset_stack_top: va, entry_point: va)
-    pub fn new_task(data: &[u8]) -> (MemorySet, usize, usize) {
+    /// return (MemorySet, user_heap_bottom: va, uset_stack_top: va, entry_point: va),
+    /// None if `data` is not a valid program
+    pub fn new_task(data: &[u8]) -> Option<(MemorySet, usize, usize, usize)> {
         let mut memory_set = MemorySet::new();
         memory_set.map_trampoline();
 
-        let elf = xmas_elf::ElfFile::new(data).expect("Invalid ELF data!");
-        assert!(
-            elf.header.pt1.magic == [0x7f, 0x45, 0x4c, 0x46],
-            "Invalid ELF data!"
-        );
+        let elf = xmas_elf::ElfFile::new(data).ok()?;
+        if elf.header.pt1.magic != [0x7f, 0x45, 0x4c, 0x46]
+            || elf.header.pt1.class() != xmas_elf::header::Class::SixtyFour
+        {
+            return None;
+        }
+        // xmas_elf reads the program headers in place without checking where they are
+        let pt2 = &elf.header.pt2;
+        let ph_table_end = (pt2.ph_count() as usize * pt2.ph_entry_size() as usize)
+            .checked_add(pt2.ph_offset() as usize)?;
+        if ph_table_end > data.len()
+            || (pt2.ph_entry_size() as usize) < size_of::<xmas_elf::program::ProgramHeader64>()
+            || pt2.ph_offset() as usize % align_of::<xmas_elf::program::ProgramHeader64>() != 0
+        {
+            return None;
+        }
         let program_header_count = elf.header.pt2.ph_count();
         let mut max_vpn: VirtPageNum = VirtPageNum::from(0);
         for i in 0..program_header_count {
-            let program_header = elf.program_header(i).unwrap();
-
-            match program_header.get_type().unwrap() {
-                xmas_elf::program::Type::Load => {
-                    let mut map_permission = MapPermission::U;
-                    let program_header_flag = program_header.flags();
+            let program_header = elf.program_header(i).ok()?;
+            match program_header.get_type() {
+                Ok(xmas_elf::program::Type::Load) => {}
+                _ => continue,
+            }
+            let mut map_permission = MapPermission::U;
+            let program_header_flag = program_header.flags();
 
-                    if program_header_flag.is_read() {
-                        map_permission |= MapPermission::R;
-                    }
-                    if program_header_flag.is_write() {
-                        map_permission |= MapPermission::W;
-                    }
-                    if program_header_flag.is_execute() {
-                        map_permission |= MapPermission::X;
-                    }
+            if program_header_flag.is_read() {
+                map_permission |= MapPermission::R;
+            }
+            if program_header_flag.is_write() {
+                map_permission |= MapPermission::W;
+            }
+            if program_header_flag.is_execute() {
+                map_permission |= MapPermission::X;
+            }
 
-                    let start_addr: VirtAddr = (program_header.virtual_addr() as usize).into();
-                    let end_addr: VirtAddr =
-                        ((program_header.virtual_addr() + program_header.mem_size()) as usize)
-                            .into();
-                    memory_set.insert_segment(
-                        MapSegment::new(start_addr, end_addr, MapType::Framed, map_permission),
-                        Some(
-                            &elf.input[program_header.offset() as usize
-                                ..(program_header.offset() + program_header.file_size()) as usize],
-                        ),
-                    );
-                    max_vpn = cmp::max(max_vpn, end_addr.pagenum_ceil());
-                }
-                _ => {
-                    continue;
-                }
+            // the file is not trusted: the content must be in it, and the segment below
+            // the mmap area, which the heap grows up to, without overlapping another one
+            let offset = program_header.offset() as usize;
+            let file_size = program_header.file_size() as usize;
+            let mem_size = program_header.mem_size() as usize;
+            let virtual_addr = program_header.virtual_addr() as usize;
+            let file_data = data.get(offset..offset.checked_add(file_size)?)?;
+            let end = virtual_addr.checked_add(mem_size)?;
+            if file_size > mem_size || end > MMAP_BASE {
+                return None;
+            }
+            let start_addr: VirtAddr = virtual_addr.into();
+            let end_addr: VirtAddr = end.into();
+            let file_end_addr: VirtAddr = (virtual_addr + file_size).into();
+            if !memory_set.is_free_area(start_addr.pagenum_floor(), end_addr.pagenum_ceil()) {
+                return None;
             }
+            memory_set.insert_segment(
+                MapSegment::new(start_addr, file_end_addr, MapType::Framed, map_permission),
+                Some(file_data),
+            );
+            // the whole pages of bss are only backed once touched
+            if file_end_addr.pagenum_ceil() < end_addr.pagenum_ceil() {
+                memory_set.insert_segment(
+                    MapSegment::new(
+                        file_end_addr.pagenum_ceil().into(),
+                        end_addr,
+                        MapType::Lazy,
+                        map_permission,
+                    ),
+                    None,
+                );
+            }
+            max_vpn = cmp::max(max_vpn, end_addr.pagenum_ceil());
         }
-        let mut user_stack_bottom: usize = VirtAddr::from(max_vpn).into();
-        user_stack_bottom += PAGE_SIZE;
-        let user_stack_top = user_stack_bottom + USER_STACK_SIZE;
+        // empty heap right above the highest load segment, grown by brk
+        let user_heap_bottom: usize = VirtAddr::from(max_vpn).into();
+        memory_set.insert_segment(
+            MapSegment::new(
+                user_heap_bottom.into(),
+                user_heap_bottom.into(),
+                MapType::Framed,
+                MapPermission::R | MapPermission::W | MapPermission::U,
+            ),
+            None,
+        );
+        let user_stack_top = USER_STACK_TOP;
+        let user_stack_bottom = user_stack_top - USER_STACK_SIZE;
         // map user stack
         memory_set.insert_segment(
             MapSegment::new(
                 user_stack_bottom.into(),
                 user_stack_top.into(),
-                MapType::Framed,
+                MapType::Lazy,
                 MapPermission::R | MapPermission::W | MapPermission::U,
             ),
             None,
@@ -219,11 +470,128 @@ impl MemorySet {
             None,
         );
 
-        (
+        Some((
             memory_set,
+            user_heap_bottom,
             user_stack_top,
             elf.header.pt2.entry_point() as usize,
-        )
+        ))
+    }
+
+    /// release all segments and the frames they hold
+    pub fn recycle_data_pages(&mut self) {
+        self.segments.clear();
+    }
+
+    /// duplicate a user address space, user pages are shared copy-on-write
+    /// with write permission stripped in both page tables
+    pub fn from_existed_user(user_space: &mut MemorySet) -> MemorySet {
+        let mut memory_set = MemorySet::new();
+        memory_set.map_trampoline();
+        for seg in user_space.segments.iter() {
+            let mut new_seg = MapSegment::from_another(seg);
+            if seg.map_type != MapType::Identical && seg.permission.contains(MapPermission::U) {
+                let pte_flags = seg.cow_pte_flags();
+                for (vpn, frame) in seg.mapping.iter() {
+                    user_space.page_table.set_flags(*vpn, pte_flags);
+                    memory_set.page_table.map(*vpn, frame.ppn, pte_flags);
+                    new_seg.mapping.insert(*vpn, frame.clone());
+                }
+                memory_set.segments.push(new_seg);
+            } else {
+                // kernel-only pages such as the trap context are copied eagerly
+                memory_set.insert_segment(new_seg, None);
+                for vpn in seg.vpn_range {
+                    let src_ppn = user_space.translate_ppn(vpn);
+                    let dst_ppn = memory_set.translate_ppn(vpn);
+                    dst_ppn
+                        .get_bytes_array_mut()
+                        .copy_from_slice(src_ppn.get_bytes_array());
+                }
+            }
+        }
+        memory_set
+    }
+
+    /// handle a page fault on a lazy or copy-on-write page,
+    /// return false if the fault can not be resolved
+    pub fn handle_page_fault(&mut self, vpn: VirtPageNum, is_write: bool) -> bool {
+        self.lazy_page_fault(vpn) || (is_write && self.cow_page_fault(vpn))
+    }
+
+    /// back a lazy page with a zeroed frame,
+    /// return false if the page is not lazy or already backed
+    pub fn lazy_page_fault(&mut self, vpn: VirtPageNum) -> bool {
+        let page_table = &mut self.page_table;
+        self.segments
+            .iter_mut()
+            .find(|seg| seg.contains(vpn))
+            .map_or(false, |seg| seg.lazy_page_fault(vpn, page_table))
+    }
+
+    /// number of pages currently backed by a frame
+    pub fn resident_pages(&self) -> usize {
+        self.segments.iter().map(|seg| seg.resident_pages()).sum()
+    }
+
+    /// number of pages covered by segments, backed or not
+    pub fn reserved_pages(&self) -> usize {
+        self.segments.iter().map(|seg| seg.page_count()).sum()
+    }
+
+    /// (start, end, permission, type) of every segment, by start address
+    pub fn segment_ranges(&self) -> Vec<(VirtAddr, VirtAddr, MapPermission, MapType)> {
+        let mut ranges: Vec<_> = self
+            .segments
+            .iter()
+            .map(|seg| {
+                (
+                    VirtAddr::from(seg.vpn_range.get_start()),
+                    VirtAddr::from(seg.vpn_range.get_end()),
+                    seg.permission,
+                    seg.map_type,
+                )
+            })
+            .collect();
+        ranges.sort_by_key(|(start, ..)| usize::from(*start));
+        ranges
+    }
+
+    /// true if every page of [start, start + len) is in a user segment allowing `permission`
+    pub fn check_user_range(&self, start: usize, len: usize, permission: MapPermission) -> bool {
+        // anything past user space would be masked into a low address by VirtAddr
+        let end = match start.checked_add(len) {
+            Some(end) if end <= USER_SPACE_TOP => end,
+            _ => return false,
+        };
+        let permission = permission | MapPermission::U;
+        let mut vpn = VirtAddr::from(start).pagenum_floor();
+        let end_vpn = VirtAddr::from(end).pagenum_ceil();
+        while vpn < end_vpn {
+            if !self
+                .segments
+                .iter()
+                .any(|seg| seg.contains(vpn) && seg.permission.contains(permission))
+            {
+                return false;
+            }
+            vpn.step();
+        }
+        true
+    }
+
+    /// handle a store page fault on a copy-on-write page,
+    /// return false if the fault is not caused by copy-on-write
+    pub fn cow_page_fault(&mut self, vpn: VirtPageNum) -> bool {
+        match self.translate_pte(vpn) {
+            Some(pte) if pte.is_valid() && !pte.is_writable() => {}
+            _ => return false,
+        }
+        let page_table = &mut self.page_table;
+        self.segments
+            .iter_mut()
+            .find(|seg| seg.contains(vpn))
+            .map_or(false, |seg| seg.cow_page_fault(vpn, page_table))
     }
 
     pub fn get_root_ppn(&self) -> PhysPageNum {
@@ -238,34 +606,14 @@ impl MemorySet {
         self.page_table.translate_pte(vpn)
     }
 
-    pub fn translate_bytes_buffer(&self, ptr: *const u8, len: usize) -> Vec<&'static mut [u8]> {
-        let mut buffers = Vec::new();
-        let mut current_va = VirtAddr::from(ptr as usize);
-        let end_va = VirtAddr::from(ptr as usize + len);
-        while current_va < end_va {
-            let mut current_vpn = current_va.pagenum_floor();
-            let current_ppn = self.translate_ppn(current_vpn);
-
-            current_vpn.step();
-
-            let current_end_va = cmp::min(VirtAddr::from(current_vpn), end_va);
-            if current_end_va.offset() == 0 {
-                buffers.push(&mut current_ppn.get_bytes_array_mut()[current_va.offset()..]);
-            } else {
-                buffers.push(
-                    &mut current_ppn.get_bytes_array_mut()
-                        [current_va.offset()..current_end_va.offset()],
-                );
-            }
-
-            current_va = current_end_va;
-        }
-        buffers
+    pub fn translate_va(&self, va: VirtAddr) -> PhysAddr {
+        let pa: PhysAddr = self.translate_ppn(va.pagenum_floor()).into();
+        (usize::from(pa) + va.offset()).into()
     }
 }
 
 pub struct MapSegment {
-    mapping: BTreeMap<VirtPageNum, FrameTracker>,
+    mapping: BTreeMap<VirtPageNum, Arc<FrameTracker>>,
     map_type: MapType,
     permission: MapPermission,
     vpn_range: VPNRange,
@@ -288,20 +636,194 @@ impl MapSegment {
         }
     }
 
+    /// create an empty segment with the same range, type and permission as `another`
+    pub fn from_another(another: &MapSegment) -> MapSegment {
+        Self {
+            mapping: BTreeMap::new(),
+            map_type: another.map_type,
+            permission: another.permission,
+            vpn_range: VPNRange::new(another.vpn_range.get_start(), another.vpn_range.get_end()),
+        }
+    }
+
     fn map_one(&mut self, vpn: VirtPageNum, page_table: &mut PageTable) {
+        assert!(self.try_map_one(vpn, page_table), "Out of frames!");
+    }
+
+    /// map one page, return false if there is no frame left
+    fn try_map_one(&mut self, vpn: VirtPageNum, page_table: &mut PageTable) -> bool {
         let ppn: PhysPageNum;
         match self.map_type {
             MapType::Identical => {
                 ppn = vpn.0.into();
             }
             MapType::Framed => {
-                let frame = frame_alloc().unwrap();
+                let frame = match frame_alloc() {
+                    Some(frame) => frame,
+                    None => return false,
+                };
                 ppn = frame.ppn;
-                self.mapping.insert(vpn, frame);
+                self.mapping.insert(vpn, Arc::new(frame));
+            }
+            MapType::Lazy => {
+                return true;
             }
         }
         let pte_flags = PTEFlags::from_bits(self.permission.bits()).unwrap();
         page_table.map(vpn, ppn, pte_flags);
+        true
+    }
+
+    /// map [end, new_end) and extend this segment, nothing is kept if frames run out
+    fn append_to(&mut self, new_end: VirtPageNum, page_table: &mut PageTable) -> bool {
+        let old_end = self.vpn_range.get_end();
+        for vpn in VPNRange::new(old_end, new_end) {
+            if !self.try_map_one(vpn, page_table) {
+                VPNRange::new(old_end, vpn)
+                    .into_iter()
+                    .for_each(|vpn| self.unmap_one(vpn, page_table));
+                return false;
+            }
+        }
+        self.vpn_range = VPNRange::new(self.vpn_range.get_start(), new_end);
+        true
+    }
+
+    /// unmap [new_end, end) and shrink this segment
+    fn shrink_to(&mut self, new_end: VirtPageNum, page_table: &mut PageTable) -> bool {
+        let old_end = self.vpn_range.get_end();
+        if new_end < self.vpn_range.get_start() || new_end > old_end {
+            return false;
+        }
+        VPNRange::new(new_end, old_end)
+            .into_iter()
+            .for_each(|vpn| self.unmap_one(vpn, page_table));
+        self.vpn_range = VPNRange::new(self.vpn_range.get_start(), new_end);
+        true
+    }
+
+    fn contains(&self, vpn: VirtPageNum) -> bool {
+        self.vpn_range.get_start() <= vpn && vpn < self.vpn_range.get_end()
+    }
+
+    fn page_count(&self) -> usize {
+        usize::from(self.vpn_range.get_end()) - usize::from(self.vpn_range.get_start())
+    }
+
+    fn resident_pages(&self) -> usize {
+        match self.map_type {
+            MapType::Identical => self.page_count(),
+            MapType::Framed | MapType::Lazy => self.mapping.len(),
+        }
+    }
+
+    /// number of pages of this segment inside [start_vpn, end_vpn)
+    fn overlap_count(&self, start_vpn: VirtPageNum, end_vpn: VirtPageNum) -> usize {
+        let start = cmp::max(self.vpn_range.get_start(), start_vpn);
+        let end = cmp::min(self.vpn_range.get_end(), end_vpn);
+        if start < end {
+            usize::from(end) - usize::from(start)
+        } else {
+            0
+        }
+    }
+
+    /// split this segment at `vpn`, self keeps [start, vpn) and [vpn, end) is returned
+    fn split_off(&mut self, vpn: VirtPageNum) -> MapSegment {
+        let right = Self {
+            mapping: self.mapping.split_off(&vpn),
+            map_type: self.map_type,
+            permission: self.permission,
+            vpn_range: VPNRange::new(vpn, self.vpn_range.get_end()),
+        };
+        self.vpn_range = VPNRange::new(self.vpn_range.get_start(), vpn);
+        right
+    }
+
+    /// change the permission of this segment and of all its mapped pages,
+    /// shared copy-on-write pages stay read-only
+    fn set_permission(&mut self, permission: MapPermission, page_table: &mut PageTable) {
+        self.permission = permission;
+        let pte_flags = PTEFlags::from_bits(permission.bits()).unwrap();
+        match self.map_type {
+            MapType::Identical => {
+                self.vpn_range
+                    .into_iter()
+                    .for_each(|vpn| page_table.set_flags(vpn, pte_flags));
+            }
+            MapType::Framed | MapType::Lazy => {
+                let cow_pte_flags = self.cow_pte_flags();
+                for (vpn, frame) in self.mapping.iter() {
+                    if Arc::strong_count(frame) > 1 {
+                        page_table.set_flags(*vpn, cow_pte_flags);
+                    } else {
+                        page_table.set_flags(*vpn, pte_flags);
+                    }
+                }
+            }
+        }
+    }
+
+    fn lazy_page_fault(&mut self, vpn: VirtPageNum, page_table: &mut PageTable) -> bool {
+        if self.map_type != MapType::Lazy || self.mapping.contains_key(&vpn) {
+            return false;
+        }
+        let frame = match frame_alloc() {
+            Some(frame) => frame,
+            None => return false,
+        };
+        page_table.map(
+            vpn,
+            frame.ppn,
+            PTEFlags::from_bits(self.permission.bits()).unwrap(),
+        );
+        self.mapping.insert(vpn, Arc::new(frame));
+        true
+    }
+
+    /// pte flags of a shared copy-on-write page
+    fn cow_pte_flags(&self) -> PTEFlags {
+        PTEFlags::from_bits((self.permission - MapPermission::W).bits()).unwrap()
+    }
+
+    /// give `vpn` a private frame, the shared one is copied unless we are its last owner
+    fn cow_page_fault(&mut self, vpn: VirtPageNum, page_table: &mut PageTable) -> bool {
+        if self.map_type == MapType::Identical || !self.permission.contains(MapPermission::W) {
+            return false;
+        }
+        let pte_flags = PTEFlags::from_bits(self.permission.bits()).unwrap();
+        let frame = match self.mapping.get(&vpn) {
+            Some(frame) => frame,
+            None => return false,
+        };
+        if Arc::strong_count(frame) == 1 {
+            page_table.set_flags(vpn, pte_flags);
+            return true;
+        }
+        let new_frame = match frame_alloc() {
+            Some(new_frame) => new_frame,
+            None => return false,
+        };
+        new_frame
+            .ppn
+            .get_bytes_array_mut()
+            .copy_from_slice(frame.ppn.get_bytes_array());
+        page_table.unmap(vpn);
+        page_table.map(vpn, new_frame.ppn, pte_flags);
+        self.mapping.insert(vpn, Arc::new(new_frame));
+        true
+    }
+
+    fn unmap_one(&mut self, vpn: VirtPageNum, page_table: &mut PageTable) {
+        match self.map_type {
+            MapType::Identical => page_table.unmap(vpn),
+            MapType::Framed | MapType::Lazy => {
+                // lazy pages which were never touched have nothing to unmap
+                if self.mapping.remove(&vpn).is_some() {
+                    page_table.unmap(vpn);
+                }
+            }
+        }
     }
 
     fn map(&mut self, page_table: &mut PageTable) {
@@ -309,6 +831,12 @@ impl MapSegment {
             self.map_one(vpn, page_table);
         })
     }
+
+    fn unmap(&mut self, page_table: &mut PageTable) {
+        self.vpn_range.clone().into_iter().for_each(|vpn| {
+            self.unmap_one(vpn, page_table);
+        })
+    }
 }
 
 pub fn remap_test() {
@@ -3,6 +3,7 @@ use self::memory_set::KERNEL_SPACE;
 pub mod heap_allocator;
 pub mod memory_set;
 pub mod page_table;
+pub mod user;
 
 pub fn init() {
     heap_allocator::init();
@@ -1,6 +1,5 @@
 use crate::hal::*;
 use crate::sync::upsafecell::UPSafeCell;
-use crate::sysconfig::MEMORY_END;
 use alloc::vec::Vec;
 use lazy_static::*;
 
@@ -14,6 +13,11 @@ trait FrameAllocator {
     fn init(&mut self, start_ppn: PhysPageNum, end_ppn: PhysPageNum);
     fn alloc(&mut self) -> Option<PhysPageNum>;
     fn dealloc(&mut self, ppn: PhysPageNum);
+    /// keep the frames of [start_ppn, end_ppn) out of use until they are released
+    fn reserve(&mut self, start_ppn: PhysPageNum, end_ppn: PhysPageNum);
+    fn release_reserved(&mut self);
+    /// (managed, free) number of frames
+    fn stats(&self) -> (usize, usize);
 }
 type FrameAllocatorImpl = StackFrameAllocator;
 
@@ -40,21 +44,40 @@ impl Drop for FrameTracker {
 }
 
 pub struct StackFrameAllocator {
+    start: usize,
     current: usize,
     end: usize,
+    /// [start, end) of the frames skipped by `current`, empty once released
+    reserved: (usize, usize),
     recycled: Vec<usize>,
 }
 
+impl StackFrameAllocator {
+    /// number of reserved frames `current` has not skipped yet
+    fn reserved_ahead(&self) -> usize {
+        if self.current <= self.reserved.0 {
+            self.reserved.1 - self.reserved.0
+        } else {
+            0
+        }
+    }
+}
+
 impl FrameAllocator for StackFrameAllocator {
     fn new() -> Self {
         StackFrameAllocator {
+            start: 0,
             current: 0,
             end: 0,
+            reserved: (0, 0),
             recycled: Vec::new(),
         }
     }
 
     fn alloc(&mut self) -> Option<PhysPageNum> {
+        if self.current == self.reserved.0 {
+            self.current = self.reserved.1;
+        }
         if let Some(ppn) = self.recycled.pop() {
             Some(ppn.into())
         } else if self.current == self.end {
@@ -66,13 +89,42 @@ impl FrameAllocator for StackFrameAllocator {
     }
 
     fn dealloc(&mut self, ppn: PhysPageNum) {
-        if ppn.0 >= self.current || self.recycled.iter().any(|&v| v == ppn.0) {
+        if ppn.0 >= self.current
+            || (self.reserved.0..self.reserved.1).contains(&ppn.0)
+            || self.recycled.iter().any(|&v| v == ppn.0)
+        {
             panic!("Frame ppn={:#x} has not been allocated!", ppn.0);
         }
         self.recycled.push(ppn.0);
     }
 
+    fn reserve(&mut self, start_ppn: PhysPageNum, end_ppn: PhysPageNum) {
+        let start = start_ppn.0.max(self.current);
+        let end = end_ppn.0.min(self.end);
+        if start < end {
+            self.reserved = (start, end);
+        }
+    }
+
+    fn release_reserved(&mut self) {
+        let (start, end) = self.reserved;
+        self.reserved = (0, 0);
+        // frames already skipped are recycled, the others are reached by `current`
+        if self.current > start {
+            self.recycled.extend(start..end);
+        }
+    }
+
+    fn stats(&self) -> (usize, usize) {
+        let reserved = self.reserved.1 - self.reserved.0;
+        (
+            self.end - self.start - reserved,
+            self.end - self.current + self.recycled.len() - self.reserved_ahead(),
+        )
+    }
+
     fn init(&mut self, start_ppn: PhysPageNum, end_ppn: PhysPageNum) {
+        self.start = start_ppn.0;
         self.current = start_ppn.0;
         self.end = end_ppn.0;
     }
@@ -83,10 +135,21 @@ pub fn frame_allocator_init() {
         fn ekernel();
     }
     let start_pa: PhysAddr = (ekernel as usize).into();
-    let end_pa: PhysAddr = MEMORY_END.into();
-    GLOBAL_FRAME_ALLOCATOR
-        .exclusive_access()
-        .init(start_pa.pagenum_ceil(), end_pa.pagenum_floor());
+    let end_pa: PhysAddr = board::memory_end().into();
+    let mut allocator = GLOBAL_FRAME_ALLOCATOR.exclusive_access();
+    allocator.init(start_pa.pagenum_ceil(), end_pa.pagenum_floor());
+    // the initramfs is read in place, its frames are used once it is unpacked
+    if let Some((start, end)) = board::initrd() {
+        allocator.reserve(
+            PhysAddr::from(start).pagenum_floor(),
+            PhysAddr::from(end).pagenum_ceil(),
+        );
+    }
+}
+
+/// hand the frames kept out by `frame_allocator_init` to the allocator
+pub fn release_reserved_frames() {
+    GLOBAL_FRAME_ALLOCATOR.exclusive_access().release_reserved();
 }
 
 pub fn frame_alloc() -> Option<FrameTracker> {
@@ -99,3 +162,8 @@ pub fn frame_alloc() -> Option<FrameTracker> {
 pub fn frame_dealloc(ppn: PhysPageNum) {
     GLOBAL_FRAME_ALLOCATOR.exclusive_access().dealloc(ppn)
 }
+
+/// (managed, free) number of frames
+pub fn frame_stats() -> (usize, usize) {
+    GLOBAL_FRAME_ALLOCATOR.exclusive_access().stats()
+}
@@ -77,6 +77,15 @@ impl PageTable {
         *pte = PageTableEntry::new(0.into(), PTEFlags::from_bits(0).unwrap());
     }
 
+    /// Replace the flags of a mapped VirtPageNum, keeping its PhysPageNum
+    pub fn set_flags(&mut self, vpn: VirtPageNum, flags: PTEFlags) {
+        let pte = self
+            .find_pte(vpn)
+            .expect("Set flags failed! Page table entry not found.");
+        assert!(pte.is_valid(), "{:?} is not mapped!", vpn);
+        *pte = PageTableEntry::new(pte.get_ppn(), flags | PTEFlags::V);
+    }
+
     pub fn translate_pte(&self, vpn: VirtPageNum) -> Option<PageTableEntry> {
         self.find_pte(vpn).map(|pte| *pte)
     }
@@ -0,0 +1,182 @@
+//! Checked access to user memory
+//!
+//! Addresses passed by a user program are never dereferenced as they are. Every page
+//! touched must be in a user segment with the permission needed, then the kernel goes
+//! through its physical address. A bad address makes the system call fail with `EFAULT`
+//! instead of bringing the kernel down.
+use crate::fs::UserBuffer;
+use crate::hal::*;
+use crate::mm::memory_set::MemorySet;
+use crate::sysconfig::PAGE_SIZE;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::cmp::min;
+use core::marker::PhantomData;
+use core::mem::{size_of, MaybeUninit};
+
+/// return value of a system call given a bad address
+pub const EFAULT: isize = -14;
+
+/// longest string taken from user space, the terminating nul excluded
+pub const MAX_CSTR_LEN: usize = 4096;
+
+/// Some of a user range is not mapped with the permission needed
+#[derive(Clone, Copy, Debug)]
+pub struct BadAddress;
+
+impl From<BadAddress> for isize {
+    fn from(_: BadAddress) -> isize {
+        EFAULT
+    }
+}
+
+/// The pages of [start, start + len) as kernel slices. Lazy pages are backed first, and
+/// shared pages unshared if the range is to be written.
+fn user_buffers(
+    memory_set: &mut MemorySet,
+    start: usize,
+    len: usize,
+    writable: bool,
+) -> Result<Vec<&'static mut [u8]>, BadAddress> {
+    if len == 0 {
+        return Ok(Vec::new());
+    }
+    let permission = if writable {
+        MapPermission::R | MapPermission::W
+    } else {
+        MapPermission::R
+    };
+    if !memory_set.check_user_range(start, len, permission) {
+        return Err(BadAddress);
+    }
+    let end = start + len;
+    let mut buffers = Vec::new();
+    let mut current = start;
+    while current < end {
+        let va = VirtAddr::from(current);
+        let vpn = va.pagenum_floor();
+        memory_set.handle_page_fault(vpn, writable);
+        // a page can still be missing if there was no frame to back or unshare it
+        let ppn = match memory_set.translate_pte(vpn) {
+            Some(pte)
+                if pte.is_valid() && pte.is_uaccessible() && (!writable || pte.is_writable()) =>
+            {
+                pte.get_ppn()
+            }
+            _ => return Err(BadAddress),
+        };
+        let len = min(PAGE_SIZE - va.offset(), end - current);
+        buffers.push(&mut ppn.get_bytes_array_mut()[va.offset()..va.offset() + len]);
+        current += len;
+    }
+    Ok(buffers)
+}
+
+/// Copy the user bytes at `src` into `dst`.
+pub fn copy_from_user(
+    memory_set: &mut MemorySet,
+    dst: &mut [u8],
+    src: usize,
+) -> Result<(), BadAddress> {
+    let mut copied = 0;
+    for buffer in user_buffers(memory_set, src, dst.len(), false)? {
+        dst[copied..copied + buffer.len()].copy_from_slice(buffer);
+        copied += buffer.len();
+    }
+    Ok(())
+}
+
+/// Copy `src` to the user bytes at `dst`.
+pub fn copy_to_user(memory_set: &mut MemorySet, dst: usize, src: &[u8]) -> Result<(), BadAddress> {
+    let buffers = user_buffers(memory_set, dst, src.len(), true)?;
+    UserBuffer::new(buffers).write_bytes(src);
+    Ok(())
+}
+
+/// Read the nul terminated string at `ptr`, it fails past `MAX_CSTR_LEN` bytes.
+/// Bytes that are not UTF-8 are replaced.
+pub fn read_cstr(memory_set: &mut MemorySet, ptr: UserPtr<u8>) -> Result<String, BadAddress> {
+    let mut bytes = Vec::new();
+    let mut current = ptr.addr;
+    while bytes.len() <= MAX_CSTR_LEN {
+        // one page at a time, the string may end before the next one is mapped
+        let len = min(
+            PAGE_SIZE - current % PAGE_SIZE,
+            MAX_CSTR_LEN + 1 - bytes.len(),
+        );
+        let chunk = user_buffers(memory_set, current, len, false)?;
+        if let Some(nul) = chunk[0].iter().position(|&byte| byte == 0) {
+            bytes.extend_from_slice(&chunk[0][..nul]);
+            return Ok(String::from_utf8_lossy(&bytes).into_owned());
+        }
+        bytes.extend_from_slice(chunk[0]);
+        current += len;
+    }
+    Err(BadAddress)
+}
+
+/// A pointer to a `T` in user space. `T` must be valid whatever its bytes, as it is
+/// read from memory the user program controls.
+pub struct UserPtr<T> {
+    addr: usize,
+    _marker: PhantomData<T>,
+}
+
+impl<T> Clone for UserPtr<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for UserPtr<T> {}
+
+impl<T: Copy> UserPtr<T> {
+    pub fn new(addr: usize) -> Self {
+        Self {
+            addr,
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn is_null(&self) -> bool {
+        self.addr == 0
+    }
+
+    pub fn read(&self, memory_set: &mut MemorySet) -> Result<T, BadAddress> {
+        let mut value = MaybeUninit::<T>::uninit();
+        let bytes = unsafe {
+            core::slice::from_raw_parts_mut(value.as_mut_ptr() as *mut u8, size_of::<T>())
+        };
+        copy_from_user(memory_set, bytes, self.addr)?;
+        Ok(unsafe { value.assume_init() })
+    }
+
+    pub fn write(&self, memory_set: &mut MemorySet, value: &T) -> Result<(), BadAddress> {
+        let bytes =
+            unsafe { core::slice::from_raw_parts(value as *const T as *const u8, size_of::<T>()) };
+        copy_to_user(memory_set, self.addr, bytes)
+    }
+}
+
+/// `len` bytes at `addr` in user space
+#[derive(Clone, Copy)]
+pub struct UserSlice {
+    addr: usize,
+    len: usize,
+}
+
+impl UserSlice {
+    pub fn new(addr: usize, len: usize) -> Self {
+        Self { addr, len }
+    }
+
+    /// the bytes, for the kernel to read them
+    pub fn readable(&self, memory_set: &mut MemorySet) -> Result<UserBuffer, BadAddress> {
+        user_buffers(memory_set, self.addr, self.len, false).map(UserBuffer::new)
+    }
+
+    /// the bytes, for the kernel to write them
+    pub fn writable(&self, memory_set: &mut MemorySet) -> Result<UserBuffer, BadAddress> {
+        user_buffers(memory_set, self.addr, self.len, true).map(UserBuffer::new)
+    }
+}
@@ -1,9 +1,6 @@
 /// size of kernel heap
 pub const KERNEL_HEAP_SIZE: usize = 0x00200000;
 
-/// physical memory end address
-pub const MEMORY_END: usize = 0x88000000;
-
 /// page size : 4KB
 pub const PAGE_SIZE: usize = 0x1000;
 
@@ -19,5 +16,23 @@ pub const TRAP_CONTEXT_BASE: usize = TRAMPOLINE - PAGE_SIZE;
 /// user app's stack size
 pub const USER_STACK_SIZE: usize = PAGE_SIZE * 2;
 
+/// end of the lower half of sv39, no user address is at or above it
+pub const USER_SPACE_TOP: usize = 0x40_0000_0000;
+
+/// top of user app's stack, a guard page below the end of user space
+pub const USER_STACK_TOP: usize = USER_SPACE_TOP - PAGE_SIZE;
+
+/// file descriptors of a task are below it
+pub const MAX_FDS: usize = 1024;
+
 /// kernel stack size
 pub const KERNEL_STACK_SIZE: usize = PAGE_SIZE * 2;
+
+/// start of the area where anonymous mmap regions are placed
+pub const MMAP_BASE: usize = 0x10_0000_0000;
+
+/// end of the mmap area, far below the trap context and trampoline
+pub const MMAP_TOP: usize = 0x20_0000_0000;
+
+/// frames of the block cache, each one holds PAGE_SIZE / 512 blocks
+pub const BLOCK_CACHE_FRAMES: usize = 32;
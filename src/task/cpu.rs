@@ -52,3 +52,10 @@ pub fn current_task() -> Option<Arc<TaskControlBlock>> {
 pub fn current_task_token_ppn() -> usize {
     PROCESSOR.exclusive_access().current_task_token_ppn()
 }
+
+pub fn current_trap_cx() -> &'static mut TrapContext {
+    current_task()
+        .expect("No current task!")
+        .inner_exclusive_access()
+        .get_trap_cx()
+}
@@ -1,10 +1,50 @@
 pub mod cpu;
 pub mod pid;
 pub mod sche;
+pub mod signal;
 pub mod task;
 
+use crate::sync::upsafecell::UPSafeCell;
 use crate::task::sche::add_task;
+use crate::task::task::{TaskControlBlock, INITPROC};
+use alloc::collections::BTreeMap;
+use alloc::sync::{Arc, Weak};
+use alloc::vec::Vec;
+use lazy_static::*;
+
+lazy_static! {
+    /// every task not reaped yet, by pid. Looking a task up does not borrow any task,
+    /// so it is safe while the current one is borrowed.
+    static ref TASKS: UPSafeCell<BTreeMap<usize, Weak<TaskControlBlock>>> =
+        unsafe { UPSafeCell::new(BTreeMap::new()) };
+}
 
 pub fn init() {
+    register_task(&INITPROC);
     add_task(task::INITPROC.clone());
 }
+
+/// make a new task visible to `find_task` and `all_tasks`
+pub fn register_task(task: &Arc<TaskControlBlock>) {
+    TASKS
+        .exclusive_access()
+        .insert(task.get_pid(), Arc::downgrade(task));
+}
+
+/// forget the task `pid`, once it is reaped
+pub fn unregister_task(pid: usize) {
+    TASKS.exclusive_access().remove(&pid);
+}
+
+/// every task not reaped yet, by increasing pid
+pub fn all_tasks() -> Vec<Arc<TaskControlBlock>> {
+    TASKS
+        .exclusive_access()
+        .values()
+        .filter_map(Weak::upgrade)
+        .collect()
+}
+
+pub fn find_task(pid: usize) -> Option<Arc<TaskControlBlock>> {
+    TASKS.exclusive_access().get(&pid)?.upgrade()
+}
@@ -1,6 +1,6 @@
 #![allow(dead_code)]
 
-use crate::hal::MapPermission;
+use crate::hal::{GenericVirtAddress, MapPermission, VirtAddr};
 use crate::misc::bitmanip::low_bit;
 use crate::mm::memory_set::{MapSegment, MapType, KERNEL_SPACE};
 use crate::println;
@@ -114,6 +114,15 @@ impl KernelStack {
     }
 }
 
+impl Drop for KernelStack {
+    fn drop(&mut self) {
+        let kstack_bottom_va: VirtAddr = self.get_kstack_bottom().into();
+        KERNEL_SPACE
+            .exclusive_access()
+            .remove_segment_with_start_vpn(kstack_bottom_va.pagenum_floor());
+    }
+}
+
 pub fn kstack_alloc_and_map(pid: &PidHandle) -> KernelStack {
     let kstack = KernelStack { id: pid.0 };
     KERNEL_SPACE.exclusive_access().insert_segment(
@@ -2,12 +2,15 @@
 
 use core::ptr::drop_in_place;
 
+use crate::drivers::block::{block_cache_stats, sync_all};
 use crate::hal::*;
+use crate::println;
 use crate::sync::upsafecell::UPSafeCell;
 use crate::task::cpu;
 use crate::task::cpu::PROCESSOR;
 use crate::task::task::TaskControlBlock;
 use crate::task::task::TaskStatus;
+use crate::task::task::INITPROC;
 use alloc::collections::VecDeque;
 use alloc::sync::Arc;
 use lazy_static::*;
@@ -55,7 +58,7 @@ pub fn run_task() {
             let mut processor = PROCESSOR.exclusive_access();
             let idle_cx = &mut processor.idle_task_cx as *mut TaskContext;
             let mut task_inner = task.inner_exclusive_access();
-            task_inner.status = TaskStatus::Ready;
+            task_inner.status = TaskStatus::Running;
             let task_cx = (&task_inner.cx) as *const TaskContext;
             drop(task_inner);
             processor.current = Some(task);
@@ -65,6 +68,57 @@ pub fn run_task() {
     }
 }
 
+/// Tasks blocked until something happens, all woken at once as they check again what
+/// they wait for
+pub struct WaitQueue {
+    queue: UPSafeCell<VecDeque<Arc<TaskControlBlock>>>,
+}
+
+impl WaitQueue {
+    pub fn new() -> Self {
+        Self {
+            queue: unsafe { UPSafeCell::new(VecDeque::new()) },
+        }
+    }
+
+    /// Block the current task until `wake_all` is called.
+    /// Nothing the waker needs may be borrowed by the caller.
+    pub fn wait(&self) {
+        let task = cpu::take_current_task().expect("No current task.");
+        let mut task_inner = task.inner_exclusive_access();
+        task_inner.status = TaskStatus::Blocked;
+        let task_cx = (&mut task_inner.cx) as *mut TaskContext;
+        drop(task_inner);
+        self.queue.exclusive_access().push_back(task.clone());
+        scheduler(task_cx);
+        // woken by a signal, the task may still be queued
+        self.queue
+            .exclusive_access()
+            .retain(|waiting| !Arc::ptr_eq(waiting, &task));
+    }
+
+    /// Put every waiting task back in the ready queue.
+    pub fn wake_all(&self) {
+        let tasks = core::mem::take(&mut *self.queue.exclusive_access());
+        for task in tasks {
+            let mut task_inner = task.inner_exclusive_access();
+            // a signal may have woken it already
+            if task_inner.status != TaskStatus::Blocked {
+                continue;
+            }
+            task_inner.status = TaskStatus::Ready;
+            drop(task_inner);
+            add_task(task);
+        }
+    }
+}
+
+impl Default for WaitQueue {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 pub fn suspend_current() {
     let current_task = cpu::take_current_task().expect("No current task.");
     let mut current_task_inner = current_task.inner_exclusive_access();
@@ -74,7 +128,43 @@ pub fn suspend_current() {
     scheduler(current_task_cx);
 }
 
-pub fn scheduler(task_cx: *mut TaskContext) -> ! {
+/// Turn the current task into a zombie and switch to the next one.
+/// Shut the machine down once the initial process exits.
+pub fn exit_current_and_run_next(exit_code: i32) {
+    let task = cpu::take_current_task().expect("No current task.");
+    if task.get_pid() == INITPROC.get_pid() {
+        println!("[kernel] initproc exited with code {}", exit_code);
+        sync_all();
+        log::debug!("block cache: {:?}", block_cache_stats());
+        shutdown(exit_code != 0);
+    }
+
+    let mut inner = task.inner_exclusive_access();
+    inner.status = TaskStatus::Zombie;
+    inner.exit_code = exit_code;
+
+    // hand the children over to initproc
+    {
+        let mut initproc_inner = INITPROC.inner_exclusive_access();
+        for child in inner.childern.iter() {
+            child.inner_exclusive_access().parent = Some(Arc::downgrade(&INITPROC));
+            initproc_inner.childern.push(child.clone());
+        }
+    }
+    inner.childern.clear();
+    // close the files now, a pipe must not stay open until the parent reaps this task
+    inner.fd_table.clear();
+    // the page table itself is released when the parent reaps this task
+    inner.memory_set.recycle_data_pages();
+    drop(inner);
+    drop(task);
+
+    // nobody will switch back to this context
+    let mut unused = TaskContext::zero_init();
+    scheduler(&mut unused as *mut TaskContext);
+}
+
+pub fn scheduler(task_cx: *mut TaskContext) {
     let processor = PROCESSOR.exclusive_access();
     let idle_cx = (&processor.idle_task_cx) as *const TaskContext;
     drop(processor);
@@ -0,0 +1,325 @@
+//! POSIX-style signals
+//!
+//! A signal sent to a task stays pending until the task goes back to user mode, where
+//! [`handle_signals`] runs its action unless the task blocks it. A handler runs on the user
+//! stack below a frame holding the interrupted trap context, and returns to the restorer
+//! registered with it, which calls sigreturn to resume the task where the signal came.
+use crate::hal::*;
+use crate::mm::user::{copy_from_user, copy_to_user};
+use crate::task::cpu::current_task;
+use crate::task::find_task;
+use crate::task::sche::{add_task, exit_current_and_run_next};
+use crate::task::task::{TaskControlBlock, TaskControlBlockInner, TaskStatus, INITPROC};
+use alloc::sync::Arc;
+use bitflags::*;
+use core::mem::size_of;
+
+/// signals are numbered from 1 to `SIGNAL_COUNT - 1`
+pub const SIGNAL_COUNT: usize = 32;
+
+/// exit code of a task terminated by a signal, plus the number of the signal
+pub const SIGNAL_EXIT_BASE: i32 = 128;
+
+/// `SignalAction::handler` of the default action
+pub const SIG_DFL: usize = 0;
+/// `SignalAction::handler` ignoring the signal
+pub const SIG_IGN: usize = 1;
+
+/// return value of a blocking call given up for a signal before doing anything
+pub const EINTR: isize = -4;
+
+/// `how` of sigprocmask
+pub const SIG_BLOCK: usize = 0;
+pub const SIG_UNBLOCK: usize = 1;
+pub const SIG_SETMASK: usize = 2;
+
+bitflags! {
+    /// A set of signals, bit n standing for signal n
+    pub struct SignalFlags: u32 {
+        const SIGHUP = 1 << 1;
+        const SIGINT = 1 << 2;
+        const SIGQUIT = 1 << 3;
+        const SIGILL = 1 << 4;
+        const SIGTRAP = 1 << 5;
+        const SIGABRT = 1 << 6;
+        const SIGBUS = 1 << 7;
+        const SIGFPE = 1 << 8;
+        const SIGKILL = 1 << 9;
+        const SIGUSR1 = 1 << 10;
+        const SIGSEGV = 1 << 11;
+        const SIGUSR2 = 1 << 12;
+        const SIGPIPE = 1 << 13;
+        const SIGALRM = 1 << 14;
+        const SIGTERM = 1 << 15;
+        const SIGSTKFLT = 1 << 16;
+        const SIGCHLD = 1 << 17;
+        const SIGCONT = 1 << 18;
+        const SIGSTOP = 1 << 19;
+        const SIGTSTP = 1 << 20;
+        const SIGTTIN = 1 << 21;
+        const SIGTTOU = 1 << 22;
+        const SIGURG = 1 << 23;
+        const SIGXCPU = 1 << 24;
+        const SIGXFSZ = 1 << 25;
+        const SIGVTALRM = 1 << 26;
+        const SIGPROF = 1 << 27;
+        const SIGWINCH = 1 << 28;
+        const SIGIO = 1 << 29;
+        const SIGPWR = 1 << 30;
+        const SIGSYS = 1 << 31;
+    }
+}
+
+/// signals that can be neither caught, ignored nor blocked
+const UNBLOCKABLE: SignalFlags =
+    SignalFlags::from_bits_truncate(SignalFlags::SIGKILL.bits() | SignalFlags::SIGSTOP.bits());
+
+/// signals whose default action is to do nothing, the others terminate the task.
+/// There is no job control, so the stop and continue signals are ignored too.
+const IGNORED_BY_DEFAULT: SignalFlags = SignalFlags::from_bits_truncate(
+    SignalFlags::SIGCHLD.bits()
+        | SignalFlags::SIGCONT.bits()
+        | SignalFlags::SIGSTOP.bits()
+        | SignalFlags::SIGTSTP.bits()
+        | SignalFlags::SIGTTIN.bits()
+        | SignalFlags::SIGTTOU.bits()
+        | SignalFlags::SIGURG.bits()
+        | SignalFlags::SIGWINCH.bits(),
+);
+
+impl SignalFlags {
+    /// the set holding only the signal `signum`, None if there is no such signal
+    pub fn from_signum(signum: usize) -> Option<Self> {
+        if (1..SIGNAL_COUNT).contains(&signum) {
+            Some(Self::from_bits_truncate(1 << signum))
+        } else {
+            None
+        }
+    }
+
+    /// number of the lowest signal of the set
+    pub fn signum(&self) -> usize {
+        self.bits().trailing_zeros() as usize
+    }
+}
+
+/// What a task does with a signal, as passed to sigaction
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct SignalAction {
+    /// `SIG_DFL`, `SIG_IGN` or the address of the handler
+    pub handler: usize,
+    /// where the handler returns, code calling sigreturn
+    pub restorer: usize,
+    /// signals blocked while the handler runs, besides the one it handles
+    pub mask: SignalFlags,
+}
+
+impl Default for SignalAction {
+    fn default() -> Self {
+        Self {
+            handler: SIG_DFL,
+            restorer: 0,
+            mask: SignalFlags::empty(),
+        }
+    }
+}
+
+/// action of every signal, indexed by signal number
+pub type SignalActions = [SignalAction; SIGNAL_COUNT];
+
+/// Pushed on the user stack when a handler is entered, popped by sigreturn
+#[repr(C)]
+struct SignalFrame {
+    /// the trap context the task is resumed with
+    cx: TrapContext,
+    /// the signal mask before the handler
+    mask: SignalFlags,
+}
+
+/// the stack pointer stays aligned on 16 bytes in the handler
+const FRAME_ALIGN: usize = 16;
+
+/// Make `signal` pending for `task`. A blocked task is woken to take it, unless it blocks
+/// the signal, so whatever it was waiting for is interrupted.
+pub fn send_signal(task: &Arc<TaskControlBlock>, signal: SignalFlags) {
+    let mut inner = task.inner_exclusive_access();
+    if inner.is_zombie() {
+        return;
+    }
+    inner.signals |= signal;
+    if inner.status == TaskStatus::Blocked && !inner.signal_mask.contains(signal) {
+        inner.status = TaskStatus::Ready;
+        drop(inner);
+        add_task(task.clone());
+    }
+}
+
+/// Send the signal `signum` to the task `pid`, a signal of 0 only checks that the task
+/// exists. initproc only takes the signals it has a handler for.
+pub fn kill(pid: usize, signum: usize) -> bool {
+    let task = match find_task(pid) {
+        Some(task) => task,
+        None => return false,
+    };
+    if signum == 0 {
+        return true;
+    }
+    let signal = match SignalFlags::from_signum(signum) {
+        Some(signal) => signal,
+        None => return false,
+    };
+    if task.get_pid() == INITPROC.get_pid()
+        && task.inner_exclusive_access().signal_actions[signum].handler == SIG_DFL
+    {
+        return true;
+    }
+    send_signal(&task, signal);
+    true
+}
+
+/// Make `signal` pending for the current task because of a fault. If the task blocks or
+/// ignores it, the default action is restored, as the task can not go on anyway.
+/// Return true if a handler will run, false if the task will be terminated.
+pub fn force_signal(signal: SignalFlags) -> bool {
+    let task = current_task().expect("No current task.");
+    let mut inner = task.inner_exclusive_access();
+    let signum = signal.signum();
+    if inner.signal_mask.contains(signal) || inner.signal_actions[signum].handler == SIG_IGN {
+        inner.signal_actions[signum] = SignalAction::default();
+        inner.signal_mask.remove(signal);
+    }
+    inner.signals |= signal;
+    inner.signal_actions[signum].handler != SIG_DFL
+}
+
+/// true if the current task has a pending signal it does not block, a blocking call should
+/// give up to let it be handled
+pub fn signal_pending() -> bool {
+    let task = current_task().expect("No current task.");
+    let inner = task.inner_exclusive_access();
+    !(inner.signals - inner.signal_mask).is_empty()
+}
+
+/// Set the action of the signal `signum` of the current task if `action` is given.
+/// Return the previous action, None if the signal does not exist or its action can not
+/// be changed.
+pub fn set_signal_action(signum: usize, action: Option<SignalAction>) -> Option<SignalAction> {
+    let signal = SignalFlags::from_signum(signum)?;
+    let task = current_task().expect("No current task.");
+    let mut inner = task.inner_exclusive_access();
+    let old_action = inner.signal_actions[signum];
+    if let Some(mut action) = action {
+        if UNBLOCKABLE.contains(signal) {
+            return None;
+        }
+        action.mask = SignalFlags::from_bits_truncate(action.mask.bits()) - UNBLOCKABLE;
+        inner.signal_actions[signum] = action;
+        // a pending signal that is now ignored is discarded
+        if action.handler == SIG_IGN
+            || (action.handler == SIG_DFL && IGNORED_BY_DEFAULT.contains(signal))
+        {
+            inner.signals.remove(signal);
+        }
+    }
+    Some(old_action)
+}
+
+/// Change the signal mask of the current task as sigprocmask does if `set` is given.
+/// Return the previous mask, None if `how` is not valid.
+pub fn change_signal_mask(how: usize, set: Option<SignalFlags>) -> Option<SignalFlags> {
+    let task = current_task().expect("No current task.");
+    let mut inner = task.inner_exclusive_access();
+    let old_mask = inner.signal_mask;
+    if let Some(set) = set {
+        let set = SignalFlags::from_bits_truncate(set.bits());
+        let mask = match how {
+            SIG_BLOCK => old_mask | set,
+            SIG_UNBLOCK => old_mask - set,
+            SIG_SETMASK => set,
+            _ => return None,
+        };
+        inner.signal_mask = mask - UNBLOCKABLE;
+    }
+    Some(old_mask)
+}
+
+/// Push a frame for the interrupted context and enter the handler of `action`.
+/// Return false if the user stack has no room for the frame.
+fn enter_handler(inner: &mut TaskControlBlockInner, signum: usize, action: &SignalAction) -> bool {
+    let cx = inner.get_trap_cx();
+    let frame_addr = match cx.user_sp().checked_sub(size_of::<SignalFrame>()) {
+        Some(addr) => addr & !(FRAME_ALIGN - 1),
+        None => return false,
+    };
+    let frame = SignalFrame {
+        cx: cx.clone(),
+        mask: inner.signal_mask,
+    };
+    let bytes = unsafe {
+        core::slice::from_raw_parts(
+            &frame as *const SignalFrame as *const u8,
+            size_of::<SignalFrame>(),
+        )
+    };
+    if copy_to_user(&mut inner.memory_set, frame_addr, bytes).is_err() {
+        return false;
+    }
+    cx.enter_signal_handler(action.handler, signum, action.restorer, frame_addr);
+    true
+}
+
+/// Run the action of the pending signals of the current task it does not block, on its
+/// way back to user mode. This does not return if the task is terminated. Once a handler
+/// is entered, the other signals wait until it returns.
+pub fn handle_signals() {
+    loop {
+        let task = current_task().expect("No current task.");
+        let mut inner = task.inner_exclusive_access();
+        let deliverable = inner.signals - inner.signal_mask;
+        if deliverable.is_empty() {
+            return;
+        }
+        let signum = deliverable.signum();
+        let signal = SignalFlags::from_bits_truncate(1 << signum);
+        inner.signals.remove(signal);
+        let action = inner.signal_actions[signum];
+        let exit_signum = match action.handler {
+            SIG_IGN => continue,
+            SIG_DFL if IGNORED_BY_DEFAULT.contains(signal) => continue,
+            SIG_DFL => signum,
+            _ => {
+                if enter_handler(&mut inner, signum, &action) {
+                    inner.signal_mask |= (action.mask | signal) - UNBLOCKABLE;
+                    return;
+                }
+                // the handler can not run without a stack
+                SignalFlags::SIGSEGV.signum()
+            }
+        };
+        drop(inner);
+        drop(task);
+        exit_current_and_run_next(SIGNAL_EXIT_BASE + exit_signum as i32);
+    }
+}
+
+/// Resume the current task with the context saved when its handler was entered, found at
+/// its stack pointer. Return false if there is no readable frame there.
+pub fn sigreturn() -> bool {
+    let task = current_task().expect("No current task.");
+    let mut inner = task.inner_exclusive_access();
+    let frame_addr = inner.get_trap_cx().user_sp();
+    let mut frame: SignalFrame = unsafe { core::mem::zeroed() };
+    let bytes = unsafe {
+        core::slice::from_raw_parts_mut(
+            &mut frame as *mut SignalFrame as *mut u8,
+            size_of::<SignalFrame>(),
+        )
+    };
+    if copy_from_user(&mut inner.memory_set, bytes, frame_addr).is_err() {
+        return false;
+    }
+    inner.get_trap_cx().restore_user_state(&frame.cx);
+    inner.signal_mask = SignalFlags::from_bits_truncate(frame.mask.bits()) - UNBLOCKABLE;
+    true
+}
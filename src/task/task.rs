@@ -1,13 +1,17 @@
 #![allow(dead_code)]
 
+use crate::fs::{open_file, File, OpenFlags, Stdin, Stdout};
 use crate::mm::memory_set::{MapSegment, MapType, MemorySet, KERNEL_SPACE};
-use crate::ramfs::get_app_data_by_name;
 use crate::sync::upsafecell::UPSafeCell;
-use crate::sysconfig::TRAP_CONTEXT_BASE;
+use crate::sysconfig::{MAX_FDS, MMAP_BASE, TRAP_CONTEXT_BASE};
 use crate::task::pid::{kstack_alloc_and_map, pid_alloc};
 use crate::task::pid::{KernelStack, PidHandle};
+use crate::task::signal::{SignalAction, SignalActions, SignalFlags, SIG_IGN};
+use crate::task::{register_task, unregister_task};
 use crate::{hal::*, print, println};
+use alloc::string::String;
 use alloc::sync::{Arc, Weak};
+use alloc::vec;
 use alloc::vec::Vec;
 use core::cell::RefMut;
 use lazy_static::*;
@@ -24,7 +28,8 @@ pub struct TaskControlBlock {
 impl TaskControlBlock {
     /// New task which is ready to run
     pub fn new(elf_data: &[u8]) -> Self {
-        let (memory_set, user_stack_top, entry_point) = MemorySet::new_task(elf_data);
+        let (memory_set, user_heap_bottom, user_stack_top, entry_point) =
+            MemorySet::new_task(elf_data).expect("Invalid ELF data!");
         let pid = pid_alloc();
         let kstack = kstack_alloc_and_map(&pid);
 
@@ -42,14 +47,121 @@ impl TaskControlBlock {
                     status: TaskStatus::Ready,
                     cx,
                     memory_set,
+                    heap_bottom: user_heap_bottom,
+                    program_brk: user_heap_bottom,
                     exit_code: 0,
                     parent: None,
                     childern: Vec::new(),
+                    cwd: String::from("/"),
+                    fd_table: vec![
+                        // 0 -> stdin
+                        Some(Arc::new(Stdin)),
+                        // 1 -> stdout
+                        Some(Arc::new(Stdout)),
+                        // 2 -> stderr
+                        Some(Arc::new(Stdout)),
+                    ],
+                    signals: SignalFlags::empty(),
+                    signal_mask: SignalFlags::empty(),
+                    signal_actions: Default::default(),
                 })
             },
         }
     }
 
+    /// Fork a child task sharing the address space of this task copy-on-write
+    pub fn fork(self: &Arc<Self>) -> Arc<Self> {
+        let mut parent_inner = self.inner_exclusive_access();
+        let memory_set = MemorySet::from_existed_user(&mut parent_inner.memory_set);
+        let trap_cx_ppn = memory_set.translate_ppn(VirtAddr::from(TRAP_CONTEXT_BASE).into());
+        let pid = pid_alloc();
+        let kstack = kstack_alloc_and_map(&pid);
+        let kstack_top = kstack.get_kstack_top();
+
+        let child = Arc::new(TaskControlBlock {
+            pid,
+            kstack,
+            inner: unsafe {
+                UPSafeCell::new(TaskControlBlockInner {
+                    trap_cx_ppn,
+                    status: TaskStatus::Ready,
+                    cx: TaskContext::goto_trap_return(kstack_top),
+                    memory_set,
+                    heap_bottom: parent_inner.heap_bottom,
+                    program_brk: parent_inner.program_brk,
+                    exit_code: 0,
+                    parent: Some(Arc::downgrade(self)),
+                    childern: Vec::new(),
+                    cwd: parent_inner.cwd.clone(),
+                    fd_table: parent_inner.fd_table.clone(),
+                    // pending signals are not inherited
+                    signals: SignalFlags::empty(),
+                    signal_mask: parent_inner.signal_mask,
+                    signal_actions: parent_inner.signal_actions,
+                })
+            },
+        });
+        parent_inner.childern.push(child.clone());
+        register_task(&child);
+        // the copied trap context still points at the parent's kernel stack
+        child
+            .inner_exclusive_access()
+            .get_trap_cx()
+            .set_kernel_sp(kstack_top);
+        child
+    }
+
+    /// Replace the address space of this task with a new program.
+    /// Return false, leaving the task as it is, if `elf_data` is not a valid program.
+    pub fn exec(&self, elf_data: &[u8]) -> bool {
+        let (memory_set, user_heap_bottom, user_stack_top, entry_point) =
+            match MemorySet::new_task(elf_data) {
+                Some(task) => task,
+                None => return false,
+            };
+        let trap_cx_ppn = memory_set.translate_ppn(VirtAddr::from(TRAP_CONTEXT_BASE).into());
+
+        let mut inner = self.inner_exclusive_access();
+        inner.memory_set = memory_set;
+        inner.trap_cx_ppn = trap_cx_ppn;
+        inner.heap_bottom = user_heap_bottom;
+        inner.program_brk = user_heap_bottom;
+        // the handlers are gone with the old program, ignored signals stay ignored
+        for action in inner.signal_actions.iter_mut() {
+            if action.handler != SIG_IGN {
+                *action = SignalAction::default();
+            }
+        }
+        *inner.get_trap_cx() =
+            TrapContext::task_init_cx(entry_point, user_stack_top, self.kstack.get_kstack_top());
+        true
+    }
+
+    /// Move the program break to `new_brk`, return the new break or None on failure
+    pub fn change_program_brk(&self, new_brk: usize) -> Option<usize> {
+        let mut inner = self.inner_exclusive_access();
+        let heap_bottom = inner.heap_bottom;
+        // the heap stays below the mmap area, the segments above can not be reached
+        if new_brk < heap_bottom || new_brk >= MMAP_BASE {
+            return None;
+        }
+        let result = if new_brk < inner.program_brk {
+            inner
+                .memory_set
+                .shrink_to(heap_bottom.into(), new_brk.into())
+        } else {
+            inner
+                .memory_set
+                .append_to(heap_bottom.into(), new_brk.into())
+        };
+        if result {
+            inner.program_brk = new_brk;
+            Some(new_brk)
+        } else {
+            None
+        }
+    }
+
     pub fn get_pid(&self) -> usize {
         self.pid.0
     }
@@ -59,6 +171,12 @@ impl TaskControlBlock {
     }
 }
 
+impl Drop for TaskControlBlock {
+    fn drop(&mut self) {
+        unregister_task(self.get_pid());
+    }
+}
+
 pub struct TaskControlBlockInner {
     /// Trap context of this task
     pub trap_cx_ppn: PhysPageNum,
@@ -68,12 +186,54 @@ pub struct TaskControlBlockInner {
     pub status: TaskStatus,
     /// Memory set of this task
     pub memory_set: MemorySet,
+    /// Bottom of the heap, right above the program image
+    pub heap_bottom: usize,
+    /// Program break, the end of the heap
+    pub program_brk: usize,
     /// Exit status of this task
     pub exit_code: i32,
     /// parent task of this task
     pub parent: Option<Weak<TaskControlBlock>>,
     /// childern of this task
     pub childern: Vec<Arc<TaskControlBlock>>,
+    /// current working directory, a canonical path
+    pub cwd: String,
+    /// file descriptor table, indexed by fd
+    pub fd_table: Vec<Option<Arc<dyn File>>>,
+    /// signals sent to this task and not handled yet
+    pub signals: SignalFlags,
+    /// signals blocked, they stay pending until unblocked
+    pub signal_mask: SignalFlags,
+    /// action of every signal
+    pub signal_actions: SignalActions,
+}
+
+impl TaskControlBlockInner {
+    pub fn get_trap_cx(&self) -> &'static mut TrapContext {
+        PhysAddr::from(self.trap_cx_ppn).get_mut()
+    }
+
+    pub fn is_zombie(&self) -> bool {
+        self.status == TaskStatus::Zombie
+    }
+
+    /// lowest free file descriptor, the table is extended if it is full,
+    /// None once `MAX_FDS` descriptors are open
+    pub fn alloc_fd(&mut self) -> Option<usize> {
+        if let Some(fd) = (0..self.fd_table.len()).find(|fd| self.fd_table[*fd].is_none()) {
+            Some(fd)
+        } else if self.fd_table.len() < MAX_FDS {
+            self.fd_table.push(None);
+            Some(self.fd_table.len() - 1)
+        } else {
+            None
+        }
+    }
+
+    /// file referred by `fd`, if it is open
+    pub fn get_file(&self, fd: usize) -> Option<Arc<dyn File>> {
+        self.fd_table.get(fd).cloned().flatten()
+    }
 }
 
 /// task status: UnInit, Ready, Running, Exited
@@ -87,9 +247,15 @@ pub enum TaskStatus {
     UnInit,
     /// Zombie
     Zombie,
+    /// Waiting in a wait queue
+    Blocked,
 }
 
 lazy_static! {
-    pub static ref INITPROC: Arc<TaskControlBlock> =
-        Arc::new(TaskControlBlock::new(get_app_data_by_name("console_out")));
+    pub static ref INITPROC: Arc<TaskControlBlock> = Arc::new(TaskControlBlock::new(
+        open_file("/", "console_out", OpenFlags::RDONLY)
+            .expect("App not found!")
+            .read_all()
+            .as_slice()
+    ));
 }